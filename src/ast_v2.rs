@@ -42,17 +42,128 @@ macro_rules! define_ast {
 define_ast!(
     Expr,
     ExprVisitor,
-    binary: Binary(m_left: Box<Expr>, m_token: TokenType, m_right: Box<Expr>),
+    binary: Binary(m_left: Box<Expr>, m_token: Token, m_right: Box<Expr>),
     grouping: Grouping(m_expression: Box<Expr>),
-    literal: Literal(m_token: TokenType),
-    unary: Unary(m_token: TokenType, m_expression: Box<Expr>),
-    variable: Variable(m_token: TokenType),
-    assign: Assign(m_token: TokenType, m_value: Box<Expr>),
-    logical: Logical(m_left: Box<Expr>, m_token: TokenType, m_right: Box<Expr>),
+    literal: Literal(m_token: Token),
+    unary: Unary(m_token: Token, m_expression: Box<Expr>),
+    variable: Variable(m_token: Token),
+    assign: Assign(m_token: Token, m_value: Box<Expr>),
+    logical: Logical(m_left: Box<Expr>, m_token: Token, m_right: Box<Expr>),
     call: Call(m_callee: Box<Expr>, m_arguments: Vec<Expr>),
-    function: Function(m_params: Vec<TokenType>, m_body: Box<Stmt>),
+    function: Function(m_params: Vec<Token>, m_body: Box<Stmt>),
+    get: Get(m_object: Box<Expr>, m_name: Token),
+    interpolation: Interpolation(m_parts: Vec<Expr>, m_span: Span),
 );
 
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary {
+                m_left, m_right, ..
+            }
+            | Expr::Logical {
+                m_left, m_right, ..
+            } => m_left.span().to(m_right.span()),
+            Expr::Grouping { m_expression } => m_expression.span(),
+            Expr::Literal { m_token } | Expr::Variable { m_token } => m_token.get_span(),
+            Expr::Unary {
+                m_token,
+                m_expression,
+            } => m_token.get_span().to(m_expression.span()),
+            Expr::Assign { m_token, m_value } => m_token.get_span().to(m_value.span()),
+            Expr::Call {
+                m_callee,
+                m_arguments,
+            } => m_arguments
+                .iter()
+                .fold(m_callee.span(), |span, argument| span.to(argument.span())),
+            Expr::Function { m_body, .. } => match m_body.as_ref() {
+                Stmt::Block { m_span, .. } => *m_span,
+                body => body.span(),
+            },
+            Expr::Get { m_object, m_name } => m_object.span().to(m_name.get_span()),
+            Expr::Interpolation { m_span, .. } => *m_span,
+        }
+    }
+}
+
+/// Appends `label` to `out` as a line of a tree, indented by `depth`.
+fn tree_node(out: &mut String, depth: usize, label: String) {
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(&label);
+    out.push('\n');
+}
+
+impl Expr {
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        match self {
+            Expr::Binary {
+                m_left,
+                m_token,
+                m_right,
+            }
+            | Expr::Logical {
+                m_left,
+                m_token,
+                m_right,
+            } => {
+                let kind = match self {
+                    Expr::Binary { .. } => "Binary",
+                    _ => "Logical",
+                };
+                tree_node(out, depth, format!("{} {:?}", kind, m_token));
+                m_left.write_tree(out, depth + 1);
+                m_right.write_tree(out, depth + 1);
+            }
+            Expr::Grouping { m_expression } => {
+                tree_node(out, depth, "Grouping".into());
+                m_expression.write_tree(out, depth + 1);
+            }
+            Expr::Literal { m_token } => tree_node(out, depth, format!("Literal {:?}", m_token)),
+            Expr::Unary {
+                m_token,
+                m_expression,
+            } => {
+                tree_node(out, depth, format!("Unary {:?}", m_token));
+                m_expression.write_tree(out, depth + 1);
+            }
+            Expr::Variable { m_token } => tree_node(out, depth, format!("Variable {}", m_token)),
+            Expr::Assign { m_token, m_value } => {
+                tree_node(out, depth, format!("Assign {}", m_token));
+                m_value.write_tree(out, depth + 1);
+            }
+            Expr::Call {
+                m_callee,
+                m_arguments,
+            } => {
+                tree_node(out, depth, "Call".into());
+                m_callee.write_tree(out, depth + 1);
+                m_arguments
+                    .iter()
+                    .for_each(|argument| argument.write_tree(out, depth + 1));
+            }
+            Expr::Function { m_params, m_body } => {
+                tree_node(
+                    out,
+                    depth,
+                    format!("Function ({})", m_params.iter().join(", ")),
+                );
+                m_body.write_tree(out, depth + 1);
+            }
+            Expr::Get { m_object, m_name } => {
+                tree_node(out, depth, format!("Get {}", m_name));
+                m_object.write_tree(out, depth + 1);
+            }
+            Expr::Interpolation { m_parts, .. } => {
+                tree_node(out, depth, "Interpolation".into());
+                m_parts
+                    .iter()
+                    .for_each(|part| part.write_tree(out, depth + 1));
+            }
+        }
+    }
+}
+
 impl Debug for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -95,6 +206,18 @@ impl Debug for Expr {
 
                 write!(f, "fun({}) {{ {:?}}} ", s, m_body)
             }
+            Expr::Get { m_object, m_name } => write!(f, "{:?}.{}", m_object, m_name),
+            Expr::Interpolation { m_parts, .. } => write!(
+                f,
+                "\"{}\"",
+                m_parts
+                    .iter()
+                    .map(|part| match part {
+                        Expr::Literal { m_token } => m_token.to_string(),
+                        part => format!("${{{:?}}}", part),
+                    })
+                    .join("")
+            ),
         }
     }
 }
@@ -102,31 +225,159 @@ impl Debug for Expr {
 define_ast!(
     Stmt,
     StmtVisitor,
-    block: Block(m_statements: Vec<Stmt>),
+    block: Block(m_statements: Vec<Stmt>, m_span: Span),
     expression: Expression(m_expression: Expr),
-    var: Var(m_name: TokenType, m_initializer: Option<Expr>),
+    var: Var(m_name: Token, m_initializer: Option<Expr>),
     r#while: While(m_condition: Expr, m_body: Box<Stmt>),
     r#if: If(m_condition: Expr, m_then_branch: Box<Stmt>, m_else_branch: Option<Box<Stmt>>),
-    function: Function(m_name: TokenType, m_params: Vec<TokenType>, m_body: Box<Stmt>),
-    r#return: Return(m_value: Option<Expr>),
+    function: Function(m_name: Token, m_params: Vec<Token>, m_body: Box<Stmt>),
+    r#return: Return(m_keyword: Token, m_value: Option<Expr>),
+    import: Import(m_path: Token, m_alias: Option<Token>, m_names: Vec<Token>),
     // class: Class(m_name: Token, m_methods: Vec<Stmt>),
 );
 
+impl Stmt {
+    /// Comments are parsed as `nil` expression statements spanning the comment text.
+    pub fn is_comment(&self, source: &str) -> bool {
+        match self {
+            Stmt::Expression { m_expression } => source
+                .get(m_expression.span().start()..)
+                .is_some_and(|text| text.starts_with("//")),
+            _ => false,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Block { m_span, .. } => *m_span,
+            Stmt::Expression { m_expression } => m_expression.span(),
+            Stmt::Var {
+                m_name,
+                m_initializer,
+            } => m_initializer
+                .iter()
+                .fold(m_name.get_span(), |span, initializer| {
+                    span.to(initializer.span())
+                }),
+            Stmt::While {
+                m_condition,
+                m_body,
+            } => m_condition.span().to(m_body.span()),
+            Stmt::If {
+                m_condition,
+                m_then_branch,
+                m_else_branch,
+            } => m_else_branch.iter().fold(
+                m_condition.span().to(m_then_branch.span()),
+                |span, branch| span.to(branch.span()),
+            ),
+            Stmt::Function { m_name, m_body, .. } => m_name.get_span().to(m_body.span()),
+            Stmt::Return { m_keyword, m_value } => m_value
+                .iter()
+                .fold(m_keyword.get_span(), |span, value| span.to(value.span())),
+            Stmt::Import {
+                m_path,
+                m_alias,
+                m_names,
+            } => m_alias
+                .iter()
+                .chain(m_names)
+                .fold(m_path.get_span(), |span, name| span.to(name.get_span())),
+        }
+    }
+}
+
+impl Stmt {
+    /// The statement as an indented tree, one node per line with its children below it.
+    pub fn tree(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        match self {
+            Stmt::Block { m_statements, .. } => {
+                tree_node(out, depth, "Block".into());
+                m_statements
+                    .iter()
+                    .for_each(|stmt| stmt.write_tree(out, depth + 1));
+            }
+            Stmt::Expression { m_expression } => {
+                tree_node(out, depth, "Expression".into());
+                m_expression.write_tree(out, depth + 1);
+            }
+            Stmt::Var {
+                m_name,
+                m_initializer,
+            } => {
+                tree_node(out, depth, format!("Var {}", m_name));
+                if let Some(initializer) = m_initializer {
+                    initializer.write_tree(out, depth + 1);
+                }
+            }
+            Stmt::While {
+                m_condition,
+                m_body,
+            } => {
+                tree_node(out, depth, "While".into());
+                m_condition.write_tree(out, depth + 1);
+                m_body.write_tree(out, depth + 1);
+            }
+            Stmt::If {
+                m_condition,
+                m_then_branch,
+                m_else_branch,
+            } => {
+                tree_node(out, depth, "If".into());
+                m_condition.write_tree(out, depth + 1);
+                m_then_branch.write_tree(out, depth + 1);
+                if let Some(else_branch) = m_else_branch {
+                    tree_node(out, depth, "Else".into());
+                    else_branch.write_tree(out, depth + 1);
+                }
+            }
+            Stmt::Function {
+                m_name,
+                m_params,
+                m_body,
+            } => {
+                tree_node(
+                    out,
+                    depth,
+                    format!("Function {}({})", m_name, m_params.iter().join(", ")),
+                );
+                m_body.write_tree(out, depth + 1);
+            }
+            Stmt::Return { m_value, .. } => {
+                tree_node(out, depth, "Return".into());
+                if let Some(value) = m_value {
+                    value.write_tree(out, depth + 1);
+                }
+            }
+            Stmt::Import {
+                m_path,
+                m_alias,
+                m_names,
+            } => match m_alias {
+                Some(alias) => tree_node(out, depth, format!("Import {:?} as {}", m_path, alias)),
+                None => tree_node(
+                    out,
+                    depth,
+                    format!("Import {:?} names {}", m_path, m_names.iter().join(", ")),
+                ),
+            },
+        }
+    }
+}
+
 impl Debug for Stmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Stmt::Block { m_statements } => {
+            Stmt::Block { m_statements, .. } => {
                 let mut s = String::new();
-                for (i, stmt) in m_statements.iter().enumerate() {
-                    if i == 0 && m_statements.len() == i + 1 {
-                        s.push_str(&format!("{:?}", stmt));
-                    } else if i == 0 {
-                        s.push_str(&format!("{:?}", stmt));
-                    } else if m_statements.len() == i + 1 {
-                        s.push_str(&format!("{:?}", stmt));
-                    } else {
-                        s.push_str(&format!("{:?}", stmt));
-                    }
+                for stmt in m_statements.iter() {
+                    s.push_str(&format!("{:?}", stmt));
                 }
 
                 write!(f, "{{ {}}} ", s)
@@ -171,10 +422,23 @@ impl Debug for Stmt {
 
                 write!(f, "fun {}({}) {{ {:?}}} ", m_name, s, m_body)
             }
-            Stmt::Return { m_value } => match m_value {
+            Stmt::Return { m_value, .. } => match m_value {
                 Some(expr) => write!(f, "return {:?}; ", expr),
                 None => write!(f, "return; "),
             },
+            Stmt::Import {
+                m_path,
+                m_alias,
+                m_names,
+            } => match m_alias {
+                Some(alias) => write!(f, "import {:?} as {}; ", m_path, alias),
+                None => write!(
+                    f,
+                    "from {:?} import {}; ",
+                    m_path,
+                    m_names.iter().join(", ")
+                ),
+            },
             // Stmt::Class { m_name, m_methods } => {
             //     let mut s = String::new();
             //     for method in m_methods {
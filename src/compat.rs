@@ -0,0 +1,53 @@
+use std::cell::Cell;
+
+/// The language the parser and evaluator follow. `Clox` matches the reference Lox from Crafting
+/// Interpreters where rlox departs from it: `var` declares variables, `print` is a statement,
+/// control flow takes any statement as its body, and `and`, `or` and `!` work on truthiness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Dialect {
+    #[default]
+    Rlox,
+    Clox,
+}
+
+thread_local! {
+    static DIALECT: Cell<Dialect> = const { Cell::new(Dialect::Rlox) };
+}
+
+pub fn dialect() -> Dialect {
+    DIALECT.with(Cell::get)
+}
+
+pub fn set_dialect(dialect: Dialect) {
+    DIALECT.with(|cell| cell.set(dialect));
+}
+
+const SHARED_KEYWORDS: &[&str] = &[
+    "and", "as", "else", "false", "for", "from", "fun", "if", "import", "nil", "or", "return",
+    "true", "while",
+];
+
+/// The keyword that declares a variable in the current dialect.
+pub fn declaration_keyword() -> &'static str {
+    match dialect() {
+        Dialect::Rlox => "let",
+        Dialect::Clox => "var",
+    }
+}
+
+/// The reserved words only the current dialect has. Each of them starts a statement.
+pub fn dialect_keywords() -> &'static [&'static str] {
+    match dialect() {
+        Dialect::Rlox => &["let"],
+        Dialect::Clox => &["var", "print"],
+    }
+}
+
+/// The reserved words of the current dialect, which cannot be used as names.
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    SHARED_KEYWORDS.iter().chain(dialect_keywords()).copied()
+}
+
+pub fn is_keyword(word: &str) -> bool {
+    SHARED_KEYWORDS.contains(&word) || dialect_keywords().contains(&word)
+}
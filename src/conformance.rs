@@ -0,0 +1,232 @@
+use crate::test_runner::*;
+
+use anyhow::Result;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// Long enough for any upstream test, short enough that a script stuck in a loop does not hold
+/// up the suite.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a script run under `--compat=clox` departed from the reference implementation.
+#[derive(Debug)]
+enum Difference {
+    Output(String),
+    RuntimeErrorMessage {
+        m_expected: String,
+        m_actual: String,
+    },
+    MissingRuntimeError(String),
+    UnexpectedRuntimeError(String),
+    MissingCompileError,
+    Rejected(String),
+    Crashed(String),
+    TimedOut,
+}
+
+impl Difference {
+    fn kind(&self) -> &'static str {
+        match self {
+            Difference::Output(_) => "output",
+            Difference::RuntimeErrorMessage { .. } => "runtime error message",
+            Difference::MissingRuntimeError(_) => "missing runtime error",
+            Difference::UnexpectedRuntimeError(_) => "unexpected runtime error",
+            Difference::MissingCompileError => "missing compile error",
+            Difference::Rejected(_) => "rejected by the parser",
+            Difference::Crashed(_) => "crashed",
+            Difference::TimedOut => "timed out",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Difference::Output(line) => line.clone(),
+            Difference::RuntimeErrorMessage {
+                m_expected,
+                m_actual,
+            } => format!("expected {:?}, got {:?}", m_expected, m_actual),
+            Difference::MissingRuntimeError(expected) => format!("expected {:?}", expected),
+            Difference::UnexpectedRuntimeError(error) | Difference::Rejected(error) => {
+                error.clone()
+            }
+            Difference::MissingCompileError => "the script ran".into(),
+            Difference::Crashed(status) => status.clone(),
+            Difference::TimedOut => format!("still running after {:?}", TIMEOUT),
+        }
+    }
+}
+
+struct Run {
+    m_status: Option<ExitStatus>,
+    m_stdout: String,
+    m_stderr: String,
+}
+
+/// Runs `script` in a child process, since a script can overflow the stack or never finish.
+fn run_isolated(script: &Path) -> Result<Run> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["--compat", "clox"])
+        .arg(script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // drain the pipes while waiting so a chatty script cannot block on a full pipe
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let stdout = std::thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+    let stderr = std::thread::spawn(move || {
+        let mut output = String::new();
+        stderr.read_to_string(&mut output).map(|_| output)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+
+    Ok(Run {
+        m_status: status,
+        m_stdout: stdout.join().unwrap()?,
+        m_stderr: stderr.join().unwrap()?,
+    })
+}
+
+/// Whether the script expects clox to reject it. Errors only one implementation reports are
+/// marked `[java line N]` or `[c line N]`; those that both report are `[line N] Error` or, on the
+/// offending line itself, `// Error at ...`.
+fn expects_compile_error(source: &str) -> bool {
+    source
+        .lines()
+        .any(|line| line.contains("// [line ") || line.contains("// Error"))
+}
+
+/// The first error on stderr, which takes one line.
+fn first_error(stderr: &str) -> String {
+    stderr.lines().next().unwrap_or_default().to_string()
+}
+
+fn check(script: &Path) -> Result<Vec<Difference>> {
+    let source = std::fs::read_to_string(script)?;
+    let expectations = Expectations::parse(&source);
+    let run = run_isolated(script)?;
+    let error = first_error(&run.m_stderr);
+
+    let mut differences = compare_output(expectations.get_output(), &run.m_stdout)
+        .into_iter()
+        .map(Difference::Output)
+        .collect::<Vec<_>>();
+
+    let compile_error = expects_compile_error(&source);
+    let runtime_error = expectations.get_runtime_error();
+    match run.m_status.map(|status| status.code()) {
+        None => differences.push(Difference::TimedOut),
+        Some(Some(0)) if compile_error => differences.push(Difference::MissingCompileError),
+        Some(Some(0)) => {
+            if let Some(expected) = runtime_error {
+                differences.push(Difference::MissingRuntimeError(expected.clone()));
+            }
+        }
+        Some(Some(65)) if compile_error => {}
+        Some(Some(65)) => differences.push(Difference::Rejected(error)),
+        Some(Some(70)) => match runtime_error {
+            Some(expected) if *expected == error => {}
+            Some(expected) => differences.push(Difference::RuntimeErrorMessage {
+                m_expected: expected.clone(),
+                m_actual: error,
+            }),
+            None if compile_error => differences.push(Difference::MissingCompileError),
+            None => differences.push(Difference::UnexpectedRuntimeError(error)),
+        },
+        Some(Some(code)) => differences.push(Difference::Crashed(format!(
+            "exited with {}: {}",
+            code,
+            run.m_stderr.trim_end()
+        ))),
+        Some(None) => differences.push(Difference::Crashed("killed by a signal".into())),
+    }
+
+    Ok(differences)
+}
+
+/// Runs every `.lox` script under `path` in clox mode and prints where rlox departs from the
+/// reference implementation, grouped by the upstream directory each test belongs to and by the
+/// kind of difference. Returns whether every script conformed.
+pub fn run_conformance(path: &Path) -> Result<bool> {
+    let mut features: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut kinds: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let (mut conforming, mut total) = (0, 0);
+
+    for script in discover(path)? {
+        if script
+            .extension()
+            .is_none_or(|extension| extension != "lox")
+        {
+            continue;
+        }
+
+        let name = script
+            .strip_prefix(path)
+            .unwrap_or(&script)
+            .display()
+            .to_string();
+        let feature = name
+            .split_once('/')
+            .map_or("(top level)", |(feature, _)| feature);
+        let differences = check(&script)?;
+
+        let counts = features.entry(feature.into()).or_default();
+        counts.1 += 1;
+        total += 1;
+        if differences.is_empty() {
+            counts.0 += 1;
+            conforming += 1;
+            println!("PASS {}", name);
+            continue;
+        }
+
+        println!("DIFF {}", name);
+        for difference in &differences {
+            println!("    {}: {}", difference.kind(), difference.describe());
+        }
+        for kind in differences.iter().map(Difference::kind).unique() {
+            kinds.entry(kind).or_default().push(name.clone());
+        }
+    }
+
+    let width = features.keys().map(String::len).max().unwrap_or_default();
+    println!();
+    println!("by feature:");
+    for (feature, (passed, total)) in &features {
+        println!("    {:<width$}  {}/{}", feature, passed, total);
+    }
+
+    if !kinds.is_empty() {
+        println!();
+        println!("by difference:");
+        for (kind, scripts) in &kinds {
+            println!("    {} ({}): {}", kind, scripts.len(), scripts.join(", "));
+        }
+    }
+
+    println!();
+    println!("{} of {} tests conform", conforming, total);
+
+    Ok(conforming == total)
+}
@@ -0,0 +1,317 @@
+use crate::ast_v2::*;
+use crate::environment::Environment;
+use crate::front_end::*;
+use crate::hook::*;
+use crate::module::file_name;
+use crate::token_v2::*;
+use crate::visitor::*;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The statements and branch points of a program, in source order.
+#[derive(Debug, Default)]
+struct Points<'a> {
+    m_source: &'a str,
+    m_statements: Vec<Span>,
+    m_branches: Vec<Span>,
+}
+
+impl ExprVisitor for Points<'_> {
+    fn visit_binary(&mut self, left: &Expr, _token: &Token, right: &Expr) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_literal(&mut self, _token: &Token) {}
+
+    fn visit_unary(&mut self, _token: &Token, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_variable(&mut self, _token: &Token) {}
+
+    fn visit_assign(&mut self, _token: &Token, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_logical(&mut self, left: &Expr, token: &Token, right: &Expr) {
+        left.accept(self);
+        self.m_branches.push(token.get_span());
+        right.accept(self);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) {
+        callee.accept(self);
+        for argument in arguments {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_function(&mut self, _params: &[Token], body: &Stmt) {
+        self.statement(body);
+    }
+
+    fn visit_get(&mut self, object: &Expr, _name: &Token) {
+        object.accept(self);
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr], _span: &Span) {
+        for part in parts {
+            part.accept(self);
+        }
+    }
+}
+
+impl StmtVisitor for Points<'_> {
+    fn visit_block(&mut self, statements: &[Stmt], _span: &Span) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_var(&mut self, _name: &Token, initializer: &Option<Expr>) {
+        if let Some(initializer) = initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
+        self.m_branches.push(condition.span());
+        condition.accept(self);
+        self.statement(body);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        self.m_branches.push(condition.span());
+        condition.accept(self);
+        self.statement(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.statement(else_branch);
+        }
+    }
+
+    fn visit_function(&mut self, _name: &Token, _params: &[Token], body: &Stmt) {
+        self.statement(body);
+    }
+
+    fn visit_return(&mut self, _keyword: &Token, value: &Option<Expr>) {
+        if let Some(value) = value {
+            value.accept(self);
+        }
+    }
+    fn visit_import(&mut self, _path: &Token, _alias: &Option<Token>, _names: &[Token]) {}
+}
+
+impl Points<'_> {
+    fn statement(&mut self, stmt: &Stmt) {
+        if !matches!(stmt, Stmt::Block { .. }) && !stmt.is_comment(self.m_source) {
+            self.m_statements.push(stmt.span());
+        }
+        stmt.accept(self);
+    }
+}
+
+/// The lines of `source` that a statement starts on, which are where execution can stop.
+pub fn statement_lines(source: &str) -> BTreeSet<usize> {
+    Coverage::points(source)
+        .m_statements
+        .iter()
+        .map(|span| span.line_col(source).0)
+        .collect()
+}
+
+/// The coverage of one file: the hit count of every line a statement starts on, and of both ways
+/// of every branch point with the line it is on.
+#[derive(Debug)]
+struct FileCoverage {
+    m_lines: BTreeMap<usize, usize>,
+    m_branches: Vec<(usize, [usize; 2])>,
+}
+
+/// Records which statements ran and which way each branch went, and reports the result against
+/// every statement and branch in the program and the modules it ran.
+#[derive(Debug)]
+pub struct Coverage {
+    m_sources: Sources,
+    m_statements: HashMap<(Option<PathBuf>, Span), usize>,
+    m_branches: HashMap<(Option<PathBuf>, Span, bool), usize>,
+}
+
+impl Coverage {
+    pub fn new(source: String) -> Coverage {
+        Coverage {
+            m_sources: Sources::new(source),
+            m_statements: HashMap::new(),
+            m_branches: HashMap::new(),
+        }
+    }
+
+    fn points(source: &str) -> Points<'_> {
+        let mut points = Points {
+            m_source: source,
+            ..Points::default()
+        };
+
+        if let Ok(stmts) = parse_program(source) {
+            for stmt in &stmts {
+                points.statement(stmt);
+            }
+        }
+
+        points
+    }
+
+    /// The coverage of `module`, or of the main script for `None`. A line is as covered as the
+    /// most executed statement on it.
+    fn file(&self, module: Option<&Path>, source: &str) -> FileCoverage {
+        let module = module.map(Path::to_path_buf);
+        let points = Coverage::points(source);
+        let line = |span: Span| span.line_col(source).0;
+
+        let mut lines = BTreeMap::new();
+        for span in &points.m_statements {
+            let key = (module.clone(), *span);
+            let hits = self.m_statements.get(&key).copied().unwrap_or_default();
+            let line = lines.entry(line(*span)).or_default();
+            *line = hits.max(*line);
+        }
+
+        let branches = points
+            .m_branches
+            .iter()
+            .map(|span| {
+                let hits = [true, false].map(|taken| {
+                    let key = (module.clone(), *span, taken);
+                    self.m_branches.get(&key).copied().unwrap_or_default()
+                });
+                (line(*span), hits)
+            })
+            .collect();
+
+        FileCoverage {
+            m_lines: lines,
+            m_branches: branches,
+        }
+    }
+
+    /// The coverage of the main script, then of each module it ran by path.
+    fn files(&self) -> Vec<(Option<&Path>, FileCoverage)> {
+        let main = (None, self.file(None, self.m_sources.get_main()));
+        let modules = self
+            .m_sources
+            .get_modules()
+            .into_iter()
+            .map(|(path, source)| (Some(path), self.file(Some(path), source)));
+
+        [main].into_iter().chain(modules).collect()
+    }
+
+    /// Renders the coverage in the lcov tracefile format, one record per file with the main
+    /// script attributed to `path`.
+    pub fn lcov(&self, path: &str) -> String {
+        let mut lcov = String::new();
+        for (module, file) in self.files() {
+            let path = module.map_or(path.into(), |module| module.display().to_string());
+            lcov.push_str(&format!("TN:\nSF:{}\n", path));
+
+            let mut branches_hit = 0;
+            for (block, (line, hits)) in file.m_branches.iter().enumerate() {
+                let evaluated = hits.iter().any(|hits| *hits > 0);
+                for (branch, hits) in hits.iter().enumerate() {
+                    // lcov marks the branches of a condition that never ran with `-`
+                    let taken = match evaluated {
+                        true => hits.to_string(),
+                        false => "-".into(),
+                    };
+                    branches_hit += usize::from(*hits > 0);
+                    lcov.push_str(&format!("BRDA:{},{},{},{}\n", line, block, branch, taken));
+                }
+            }
+            lcov.push_str(&format!(
+                "BRF:{}\nBRH:{}\n",
+                file.m_branches.len() * 2,
+                branches_hit
+            ));
+
+            for (line, hits) in &file.m_lines {
+                lcov.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            lcov.push_str(&format!(
+                "LF:{}\nLH:{}\nend_of_record\n",
+                file.m_lines.len(),
+                file.m_lines.values().filter(|hits| **hits > 0).count()
+            ));
+        }
+
+        lcov
+    }
+
+    /// Totals the coverage of every file. Uncovered lines in a module are listed as `file:line`.
+    pub fn summary(&self) -> String {
+        let (mut lines, mut lines_hit, mut branches, mut branches_hit) = (0, 0, 0, 0);
+        let mut uncovered = Vec::new();
+        for (module, file) in self.files() {
+            lines += file.m_lines.len();
+            lines_hit += file.m_lines.values().filter(|hits| **hits > 0).count();
+            branches += file.m_branches.len() * 2;
+            branches_hit += file
+                .m_branches
+                .iter()
+                .flat_map(|(_, hits)| hits)
+                .filter(|hits| **hits > 0)
+                .count();
+
+            let missed = file.m_lines.iter().filter(|(_, hits)| **hits == 0);
+            uncovered.extend(missed.map(|(line, _)| match module {
+                Some(module) => format!("{}:{}", file_name(module), line),
+                None => line.to_string(),
+            }));
+        }
+
+        let percent = |hit: usize, total: usize| match total {
+            0 => 100.0,
+            total => hit as f64 * 100.0 / total as f64,
+        };
+
+        let mut summary = format!(
+            "lines:    {}/{} ({:.1}%)\nbranches: {}/{} ({:.1}%)\n",
+            lines_hit,
+            lines,
+            percent(lines_hit, lines),
+            branches_hit,
+            branches,
+            percent(branches_hit, branches),
+        );
+
+        if !uncovered.is_empty() {
+            summary.push_str(&format!("uncovered lines: {}\n", uncovered.join(", ")));
+        }
+
+        summary
+    }
+}
+
+impl ExecutionHook for Coverage {
+    fn on_statement(&mut self, stmt: &Stmt, env: &Rc<RefCell<Environment>>) {
+        let module = env.borrow().get_module();
+        // keeps the module's source for the report
+        self.m_sources.get(module.as_deref());
+        *self.m_statements.entry((module, stmt.span())).or_default() += 1;
+    }
+
+    fn on_branch(&mut self, span: Span, taken: bool, env: &Rc<RefCell<Environment>>) {
+        let module = env.borrow().get_module();
+        *self.m_branches.entry((module, span, taken)).or_default() += 1;
+    }
+}
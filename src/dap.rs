@@ -0,0 +1,388 @@
+use crate::ast_v2::Stmt;
+use crate::coverage::statement_lines;
+use crate::debugger::*;
+use crate::environment::Environment;
+use crate::hook::*;
+use crate::interpreter::*;
+use crate::lsp::{read_message, write_message};
+use crate::module::file_name;
+use crate::value::{Callable, Value};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const THREAD_ID: i64 = 1;
+
+enum Action {
+    None,
+    Resume,
+    Disconnect,
+}
+
+/// Writes protocol messages, numbering each with the `seq` the protocol requires.
+struct Channel {
+    m_output: Box<dyn Write>,
+    m_seq: i64,
+}
+
+impl Channel {
+    fn send(&mut self, mut message: Json) -> Result<()> {
+        self.m_seq += 1;
+        message["seq"] = json!(self.m_seq);
+        write_message(&mut self.m_output, &message)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+/// Forwards program output to the client as `output` events, since stdout carries the protocol.
+struct OutputEvents(Rc<RefCell<Channel>>);
+
+impl Write for OutputEvents {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .borrow_mut()
+            .event(
+                "output",
+                json!({ "category": "stdout", "output": String::from_utf8_lossy(buf) }),
+            )
+            .map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A debug adapter speaking the Debug Adapter Protocol with `Content-Length` framing. The
+/// program runs on the calling thread, and requests are read from inside the execution hooks
+/// while it is paused.
+pub struct DebugAdapter {
+    m_input: Box<dyn BufRead>,
+    m_channel: Rc<RefCell<Channel>>,
+    m_session: Session,
+    m_program: Option<String>,
+    m_configured: bool,
+    m_variables: Vec<Vec<(String, Value)>>,
+    // set when the client goes away or the protocol fails while the program is paused, which
+    // stops the program
+    m_disconnected: bool,
+    m_error: Option<anyhow::Error>,
+}
+
+impl DebugAdapter {
+    /// Serves a single debug session: configures and launches the program, runs it to
+    /// completion and then waits for the client to disconnect.
+    pub fn run(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Result<()> {
+        let channel = Rc::new(RefCell::new(Channel {
+            m_output: output,
+            m_seq: 0,
+        }));
+        let adapter = Rc::new(RefCell::new(DebugAdapter {
+            m_input: input,
+            m_channel: channel.clone(),
+            m_session: Session::new(String::new()),
+            m_program: None,
+            m_configured: false,
+            m_variables: Vec::new(),
+            m_disconnected: false,
+            m_error: None,
+        }));
+
+        // the client sends breakpoints between launch and configurationDone, so the program
+        // only starts once both have arrived
+        loop {
+            let mut adapter = adapter.borrow_mut();
+            if adapter.m_program.is_some() && adapter.m_configured {
+                break;
+            }
+            if let None | Some(Action::Disconnect) = adapter.next_request()? {
+                return Ok(());
+            }
+        }
+
+        let source = adapter.borrow().m_session.get_source().to_string();
+        let program = adapter.borrow().m_program.clone().unwrap_or_default();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_path(Path::new(&program));
+        interpreter.set_output(Box::new(OutputEvents(channel.clone())));
+        interpreter.set_hook(adapter.clone());
+        let exit_code = interpreter
+            .interpret(source)
+            .map_or_else(|err| err.exit_code(), |_| 0);
+
+        if let Some(err) = adapter.borrow_mut().m_error.take() {
+            return Err(err);
+        }
+        if adapter.borrow().m_disconnected {
+            return Ok(());
+        }
+
+        channel
+            .borrow_mut()
+            .event("exited", json!({ "exitCode": exit_code }))?;
+        channel.borrow_mut().event("terminated", json!({}))?;
+
+        loop {
+            if let None | Some(Action::Disconnect) = adapter.borrow_mut().next_request()? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads and answers one request, returning `None` once the client closes the stream.
+    fn next_request(&mut self) -> Result<Option<Action>> {
+        let message = match read_message(&mut self.m_input)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+
+        let command = message["command"].as_str().unwrap_or_default().to_string();
+        let (body, action) = match self.handle_request(&command, &message["arguments"]) {
+            Ok((body, action)) => (Ok(body), action),
+            Err(err) => (Err(err), Action::None),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": message["seq"],
+            "command": command,
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(err) => response["message"] = json!(err.to_string()),
+        }
+        self.m_channel.borrow_mut().send(response)?;
+
+        if command == "initialize" {
+            self.m_channel
+                .borrow_mut()
+                .event("initialized", json!({}))?;
+        }
+
+        Ok(Some(action))
+    }
+
+    fn handle_request(&mut self, command: &str, args: &Json) -> Result<(Json, Action)> {
+        match command {
+            "initialize" => Ok((
+                json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                }),
+                Action::None,
+            )),
+            "launch" => {
+                let program = args["program"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Missing program to launch"))?;
+                let source = std::fs::read_to_string(program)?;
+
+                let breakpoints = self.m_session.get_breakpoints().clone();
+                self.m_session = Session::new(source);
+                breakpoints
+                    .into_iter()
+                    .for_each(|line| self.m_session.set_breakpoint(line));
+                if !args["stopOnEntry"].as_bool().unwrap_or(false) {
+                    self.m_session.resume(StepMode::Continue);
+                }
+
+                self.m_program = Some(program.into());
+                Ok((json!({}), Action::None))
+            }
+            "setBreakpoints" => {
+                let lines = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect::<Vec<_>>();
+
+                // breakpoints hold only on lines of the program that have a statement
+                let path = args["source"]["path"].as_str().unwrap_or_default();
+                let statements = match self.is_program(path) {
+                    true => statement_lines(&std::fs::read_to_string(path)?),
+                    false => BTreeSet::new(),
+                };
+
+                self.m_session.clear_breakpoints();
+                let breakpoints = lines
+                    .iter()
+                    .map(|line| match statements.contains(line) {
+                        true => {
+                            self.m_session.set_breakpoint(*line);
+                            json!({ "verified": true, "line": line })
+                        }
+                        false => json!({
+                            "verified": false,
+                            "line": line,
+                            "message": "No statement on this line of the program",
+                        }),
+                    })
+                    .collect::<Vec<_>>();
+                Ok((json!({ "breakpoints": breakpoints }), Action::None))
+            }
+            "configurationDone" => {
+                self.m_configured = true;
+                Ok((json!({}), Action::None))
+            }
+            "threads" => Ok((
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+                Action::None,
+            )),
+            "stackTrace" => {
+                let program = self.m_program.as_ref().map(PathBuf::from);
+                let frames = self
+                    .m_session
+                    .get_frames()
+                    .enumerate()
+                    .map(|(index, frame)| {
+                        // a frame running a module's code points the client at the module
+                        let source = frame.get_module().or(program.as_deref()).map(|path| {
+                            json!({ "name": file_name(path), "path": path.display().to_string() })
+                        });
+                        json!({
+                            "id": index,
+                            "name": frame.get_name(),
+                            "line": frame.get_line(),
+                            "column": 1,
+                            "source": source,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok((
+                    json!({ "totalFrames": frames.len(), "stackFrames": frames }),
+                    Action::None,
+                ))
+            }
+            "scopes" => {
+                let frame = args["frameId"].as_u64().unwrap_or_default() as usize;
+                let scopes = self.m_session.scopes(frame);
+                let count = scopes.len();
+
+                let scopes = scopes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, scope)| {
+                        self.m_variables.push(scope);
+                        json!({
+                            "name": match index {
+                                index if index + 1 == count => "Globals".to_string(),
+                                0 => "Locals".to_string(),
+                                index => format!("Scope {}", index),
+                            },
+                            "variablesReference": self.m_variables.len(),
+                            "expensive": false,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok((json!({ "scopes": scopes }), Action::None))
+            }
+            "variables" => {
+                let reference = args["variablesReference"].as_u64().unwrap_or_default() as usize;
+                let variables = self
+                    .m_variables
+                    .get(reference.wrapping_sub(1))
+                    .ok_or_else(|| anyhow!("Invalid variables reference => {}", reference))?
+                    .iter()
+                    .map(|(name, value)| {
+                        json!({
+                            "name": name,
+                            "value": format!("{:?}", value),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok((json!({ "variables": variables }), Action::None))
+            }
+            "evaluate" => {
+                let frame = args["frameId"].as_u64().unwrap_or_default() as usize;
+                let expression = args["expression"].as_str().unwrap_or_default();
+                match self.m_session.evaluate(frame, expression) {
+                    Ok(value) => Ok((
+                        json!({ "result": format!("{:?}", value), "variablesReference": 0 }),
+                        Action::None,
+                    )),
+                    Err(err) => Err(anyhow!(err.join("\n"))),
+                }
+            }
+            "continue" => Ok(self.resume(StepMode::Continue)),
+            "next" => Ok(self.resume(StepMode::StepOver)),
+            "stepIn" => Ok(self.resume(StepMode::StepIn)),
+            "stepOut" => Ok(self.resume(StepMode::StepOut)),
+            "disconnect" => Ok((json!({}), Action::Disconnect)),
+            command => Err(anyhow!("Unhandled command => {}", command)),
+        }
+    }
+
+    /// Whether `path` is the launched program, or could be before the launch request arrives.
+    fn is_program(&self, path: &str) -> bool {
+        match &self.m_program {
+            Some(program) => std::fs::canonicalize(path)
+                .is_ok_and(|path| std::fs::canonicalize(program).is_ok_and(|p| p == path)),
+            None => Path::new(path).is_file(),
+        }
+    }
+
+    fn resume(&mut self, mode: StepMode) -> (Json, Action) {
+        self.m_session.resume(mode);
+        self.m_variables.clear();
+        (json!({ "allThreadsContinued": true }), Action::Resume)
+    }
+
+    /// Tells the client where execution stopped and serves requests until it resumes or
+    /// disconnects.
+    fn pause(&mut self, reason: StopReason) -> Result<()> {
+        self.m_channel.borrow_mut().event(
+            "stopped",
+            json!({ "reason": reason.name(), "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+
+        loop {
+            match self.next_request()? {
+                Some(Action::Resume) => return Ok(()),
+                Some(Action::None) => {}
+                None | Some(Action::Disconnect) => {
+                    self.m_disconnected = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl ExecutionHook for DebugAdapter {
+    fn on_statement(&mut self, stmt: &Stmt, env: &Rc<RefCell<Environment>>) {
+        if self.is_stopped() {
+            return;
+        }
+
+        if let Some(reason) = self.m_session.pause_reason(stmt, env) {
+            if let Err(err) = self.pause(reason) {
+                self.m_error = Some(err);
+            }
+        }
+    }
+
+    fn on_call(&mut self, callable: &Callable, _arguments: &[Value]) {
+        self.m_session.enter(&callable.name());
+    }
+
+    fn on_return(&mut self, _callable: &Callable, _result: &Result<Value, Vec<String>>) {
+        self.m_session.exit();
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.m_disconnected || self.m_error.is_some()
+    }
+}
@@ -0,0 +1,443 @@
+use crate::ast_v2::*;
+use crate::environment::Environment;
+use crate::front_end::*;
+use crate::hook::*;
+use crate::module::file_name;
+use crate::value::*;
+use crate::visitor::*;
+
+use itertools::Itertools;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    m_name: String,
+    m_line: usize,
+    m_env: Rc<RefCell<Environment>>,
+    m_module: Option<PathBuf>,
+    m_source: Rc<str>,
+}
+
+impl Frame {
+    pub fn get_name(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.m_line
+    }
+
+    /// The file of the module the frame is running, or `None` in the main script.
+    pub fn get_module(&self) -> Option<&Path> {
+        self.m_module.as_deref()
+    }
+
+    /// The current line, with the module's file name outside of the main script.
+    pub fn get_location(&self) -> String {
+        match &self.m_module {
+            Some(module) => format!("line {} of {}", self.m_line, file_name(module)),
+            None => format!("line {}", self.m_line),
+        }
+    }
+
+    /// A line of the file the frame is running.
+    pub fn source_line(&self, line: usize) -> &str {
+        self.m_source
+            .lines()
+            .nth(line.saturating_sub(1))
+            .unwrap_or("")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepMode {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+impl StopReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StopReason::Entry => "entry",
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+        }
+    }
+}
+
+/// Execution state shared by the debugger front ends: the call stack, the breakpoints and the
+/// stepping mode that decide where execution pauses next.
+#[derive(Debug)]
+pub struct Session {
+    m_sources: Sources,
+    m_breakpoints: BTreeSet<usize>,
+    m_frames: Vec<Frame>,
+    m_mode: StepMode,
+    m_pause_depth: usize,
+    m_pause_line: (Option<PathBuf>, usize),
+    m_last_line: (Option<PathBuf>, usize),
+}
+
+impl Session {
+    pub fn new(source: String) -> Session {
+        Session {
+            m_sources: Sources::new(source),
+            m_breakpoints: BTreeSet::new(),
+            m_frames: Vec::new(),
+            m_mode: StepMode::StepIn,
+            m_pause_depth: 0,
+            m_pause_line: (None, 0),
+            m_last_line: (None, 0),
+        }
+    }
+
+    /// The source of the main script.
+    pub fn get_source(&self) -> &str {
+        self.m_sources.get_main()
+    }
+
+    pub fn get_breakpoints(&self) -> &BTreeSet<usize> {
+        &self.m_breakpoints
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.m_breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.m_breakpoints.remove(&line);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.m_breakpoints.clear();
+    }
+
+    /// The call stack, innermost frame first.
+    pub fn get_frames(&self) -> impl Iterator<Item = &Frame> {
+        self.m_frames.iter().rev()
+    }
+
+    pub fn resume(&mut self, mode: StepMode) {
+        self.m_mode = mode;
+        self.m_pause_depth = self.m_frames.len();
+        self.m_pause_line = self
+            .m_frames
+            .last()
+            .map_or((None, 0), |frame| (frame.m_module.clone(), frame.m_line));
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        let frame = match self.m_frames.last() {
+            Some(frame) => Frame {
+                m_name: name.into(),
+                m_line: 0,
+                ..frame.clone()
+            },
+            None => Frame {
+                m_name: name.into(),
+                m_line: 0,
+                m_env: Environment::new(),
+                m_module: None,
+                m_source: self.m_sources.get(None),
+            },
+        };
+
+        self.m_frames.push(frame);
+    }
+
+    pub fn exit(&mut self) {
+        self.m_frames.pop();
+    }
+
+    /// Records that a statement is about to run and decides whether execution should pause on
+    /// it, and why.
+    pub fn pause_reason(
+        &mut self,
+        stmt: &Stmt,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Option<StopReason> {
+        if self.m_frames.is_empty() {
+            self.enter("<script>");
+        }
+
+        let module = env.borrow().get_module();
+        let source = self.m_sources.get(module.as_deref());
+        let (line, _) = stmt.span().line_col(&source);
+        let location = (module, line);
+        // breakpoints are set on lines of the main script
+        let at_breakpoint = location.0.is_none()
+            && self.m_breakpoints.contains(&line)
+            && location != self.m_last_line;
+        self.m_last_line = location.clone();
+
+        let frame = self.m_frames.last_mut().unwrap();
+        frame.m_env = env.clone();
+
+        // blocks only group other statements, and comments are parsed as empty statements, so
+        // neither is somewhere a user would expect to stop
+        if matches!(stmt, Stmt::Block { .. }) || stmt.is_comment(&source) {
+            return None;
+        }
+        frame.m_line = line;
+        frame.m_module = location.0.clone();
+        frame.m_source = source;
+
+        let depth = self.m_frames.len();
+        let moved = location != self.m_pause_line || depth != self.m_pause_depth;
+        let step = match self.m_mode {
+            StepMode::Continue => false,
+            StepMode::StepIn => moved,
+            StepMode::StepOver => moved && depth <= self.m_pause_depth,
+            StepMode::StepOut => depth < self.m_pause_depth,
+        };
+
+        match (at_breakpoint, step) {
+            (true, _) => Some(StopReason::Breakpoint),
+            // nothing has resumed execution yet, so this is the first statement of the program
+            (false, true) if self.m_pause_depth == 0 => Some(StopReason::Entry),
+            (false, true) => Some(StopReason::Step),
+            (false, false) => None,
+        }
+    }
+
+    /// Evaluates an expression in the environment of a frame, counted from the innermost.
+    pub fn evaluate(&self, frame: usize, expression: &str) -> Result<Value, Vec<String>> {
+        let env = match self.get_frames().nth(frame) {
+            Some(frame) => frame.m_env.clone(),
+            None => return Err(vec![format!("Invalid frame => {}", frame)]),
+        };
+
+        let source = format!("{};", expression.trim().trim_end_matches(';'));
+        match parse_program(&source) {
+            Ok(stmts) => match stmts.as_slice() {
+                [Stmt::Expression { m_expression }] => {
+                    let mut visitor = ExprEvaluator::new(&env, &None);
+                    m_expression.accept(&mut visitor);
+                    visitor.get_result()
+                }
+                _ => Err(vec![format!("Expected an expression => {}", expression)]),
+            },
+            Err(err) => Err(err.iter().map(|err| err.to_string()).collect()),
+        }
+    }
+
+    /// The variables visible from a frame, one list per `Environment` scope from the innermost
+    /// outwards. Native functions are left out of the global scope.
+    pub fn scopes(&self, frame: usize) -> Vec<Vec<(String, Value)>> {
+        let mut scopes = Vec::new();
+        let mut env = self
+            .get_frames()
+            .nth(frame)
+            .map(|frame| frame.m_env.clone());
+
+        while let Some(scope) = env {
+            scopes.push(
+                scope
+                    .borrow()
+                    .get_scope()
+                    .iter()
+                    .filter(|(_, value)| {
+                        !matches!(value, Value::Callable(Callable::NativeFunction(..)))
+                    })
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .collect(),
+            );
+            env = scope.borrow().get_parent();
+        }
+
+        scopes
+    }
+}
+
+const HELP: &str = "\
+commands:
+    b, break [line]      set a breakpoint, or list them without a line
+    d, delete <line>     remove a breakpoint
+    s, step              step into the next statement
+    n, next              step over function calls
+    o, out               run until the current function returns
+    c, continue          run until the next breakpoint
+    bt, backtrace        print the call stack
+    f, frame <n>         select a frame from the backtrace
+    v, vars              print the variables of each scope in the selected frame
+    p, print <expr>      evaluate an expression in the selected frame
+    l, list              print the source around the current line
+    q, quit              stop the program
+    h, help              print this message";
+
+/// A terminal front end for stepping through a program, reading commands from stdin whenever
+/// execution pauses.
+#[derive(Debug)]
+pub struct Debugger {
+    m_session: Session,
+    m_frame: usize,
+    m_quit: bool,
+}
+
+impl Debugger {
+    pub fn new(source: String) -> Debugger {
+        Debugger {
+            m_session: Session::new(source),
+            m_frame: 0,
+            m_quit: false,
+        }
+    }
+
+    fn print_location(&self) {
+        if let Some(frame) = self.m_session.get_frames().next() {
+            println!(
+                "stopped in {} at {}",
+                frame.get_name(),
+                frame.get_location()
+            );
+            println!(
+                "{:>5} | {}",
+                frame.get_line(),
+                frame.source_line(frame.get_line())
+            );
+        }
+    }
+
+    /// Reads commands until one resumes execution, returning `false` when the user quits or
+    /// input runs out.
+    fn prompt(&mut self) -> bool {
+        self.m_frame = 0;
+        self.print_location();
+
+        loop {
+            print!("(debug) ");
+            std::io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            if std::io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+                return false;
+            }
+
+            let (command, argument) = match input.trim().split_once(' ') {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (input.trim(), ""),
+            };
+
+            match command {
+                "s" | "step" => return self.resume(StepMode::StepIn),
+                "n" | "next" => return self.resume(StepMode::StepOver),
+                "o" | "out" => return self.resume(StepMode::StepOut),
+                "c" | "continue" => return self.resume(StepMode::Continue),
+                "q" | "quit" => return false,
+                "b" | "break" if argument.is_empty() => {
+                    for line in self.m_session.get_breakpoints() {
+                        println!("breakpoint at line {}", line);
+                    }
+                }
+                "b" | "break" => match argument.parse::<usize>() {
+                    Ok(line) => {
+                        self.m_session.set_breakpoint(line);
+                        println!("breakpoint set at line {}", line);
+                    }
+                    Err(_) => println!("ERROR: Expected a line number => {}", argument),
+                },
+                "d" | "delete" => match argument.parse::<usize>() {
+                    Ok(line) => {
+                        self.m_session.clear_breakpoint(line);
+                        println!("breakpoint removed from line {}", line);
+                    }
+                    Err(_) => println!("ERROR: Expected a line number => {}", argument),
+                },
+                "bt" | "backtrace" => {
+                    for (index, frame) in self.m_session.get_frames().enumerate() {
+                        println!(
+                            "{} #{} {} at {}",
+                            if index == self.m_frame { "*" } else { " " },
+                            index,
+                            frame.get_name(),
+                            frame.get_location()
+                        );
+                    }
+                }
+                "f" | "frame" => match argument.parse::<usize>() {
+                    Ok(frame) if frame < self.m_session.get_frames().count() => {
+                        self.m_frame = frame;
+                    }
+                    _ => println!("ERROR: Invalid frame => {}", argument),
+                },
+                "v" | "vars" => {
+                    let scopes = self.m_session.scopes(self.m_frame);
+                    for (index, scope) in scopes.iter().enumerate() {
+                        if index + 1 == scopes.len() {
+                            println!("globals:");
+                        } else {
+                            println!("scope {}:", index);
+                        }
+                        for (name, value) in scope {
+                            println!("    {} = {:?}", name, value);
+                        }
+                    }
+                }
+                "p" | "print" => match self.m_session.evaluate(self.m_frame, argument) {
+                    Ok(value) => println!("{:?}", value),
+                    Err(err) => err.iter().for_each(|err| println!("ERROR: {}", err)),
+                },
+                "l" | "list" => {
+                    if let Some(frame) = self.m_session.get_frames().nth(self.m_frame) {
+                        let current = frame.get_line();
+                        for line in current.saturating_sub(3).max(1)..=current + 3 {
+                            println!(
+                                "{} {:>4} | {}",
+                                if line == current { ">" } else { " " },
+                                line,
+                                frame.source_line(line)
+                            );
+                        }
+                    }
+                }
+                "h" | "help" => println!("{}", HELP),
+                "" => {}
+                command => println!("ERROR: Unknown command => {}", command),
+            }
+        }
+    }
+
+    fn resume(&mut self, mode: StepMode) -> bool {
+        self.m_session.resume(mode);
+        true
+    }
+
+    /// Whether the user quit, leaving the rest of the program unrun.
+    pub fn has_quit(&self) -> bool {
+        self.m_quit
+    }
+}
+
+impl ExecutionHook for Debugger {
+    fn on_statement(&mut self, stmt: &Stmt, env: &Rc<RefCell<Environment>>) {
+        if !self.m_quit && self.m_session.pause_reason(stmt, env).is_some() {
+            self.m_quit = !self.prompt();
+        }
+    }
+
+    fn on_call(&mut self, callable: &Callable, _arguments: &[Value]) {
+        self.m_session.enter(&callable.name());
+    }
+
+    fn on_return(&mut self, _callable: &Callable, _result: &Result<Value, Vec<String>>) {
+        self.m_session.exit();
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.m_quit
+    }
+}
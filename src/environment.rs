@@ -3,12 +3,15 @@ use crate::value::Value;
 use anyhow::Result;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 #[derive(Debug)]
 pub struct Environment {
     m_scope: HashMap<String, Value>,
     m_parent: Option<Rc<RefCell<Environment>>>,
+    m_path: Option<PathBuf>,
+    m_module: bool,
 }
 
 impl Environment {
@@ -16,6 +19,8 @@ impl Environment {
         Rc::new(RefCell::new(Environment {
             m_scope: HashMap::new(),
             m_parent: None,
+            m_path: None,
+            m_module: false,
         }))
     }
 
@@ -23,6 +28,8 @@ impl Environment {
         Rc::new(RefCell::new(Environment {
             m_scope: HashMap::new(),
             m_parent: Some(parent.clone()),
+            m_path: None,
+            m_module: false,
         }))
     }
 
@@ -43,6 +50,59 @@ impl Environment {
         }
     }
 
+    pub fn get_scope(&self) -> &HashMap<String, Value> {
+        &self.m_scope
+    }
+
+    /// Every name visible from this scope, including those of the enclosing scopes.
+    pub fn get_names(&self) -> Vec<String> {
+        let mut names = self.m_scope.keys().cloned().collect::<Vec<_>>();
+        if let Some(parent) = &self.m_parent {
+            names.extend(parent.borrow().get_names());
+        }
+        names
+    }
+
+    pub fn get_parent(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.m_parent.clone()
+    }
+
+    /// Marks this environment as the top level of the script or module at `path`.
+    pub fn set_path(&mut self, path: &Path) {
+        self.m_path = Some(path.to_path_buf());
+    }
+
+    /// Sets the file this environment belongs to, or none, and returns the one it had.
+    pub fn replace_path(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        std::mem::replace(&mut self.m_path, path)
+    }
+
+    /// Marks this environment as the top level of an imported module.
+    pub fn set_module(&mut self) {
+        self.m_module = true;
+    }
+
+    /// The file of the imported module this environment belongs to, or `None` in the main
+    /// script.
+    pub fn get_module(&self) -> Option<PathBuf> {
+        match self.m_module {
+            true => self.m_path.clone(),
+            false => self
+                .m_parent
+                .as_ref()
+                .and_then(|parent| parent.borrow().get_module()),
+        }
+    }
+
+    /// The file of the script or module this environment belongs to, if it came from one.
+    pub fn get_path(&self) -> Option<PathBuf> {
+        self.m_path.clone().or_else(|| {
+            self.m_parent
+                .as_ref()
+                .and_then(|parent| parent.borrow().get_path())
+        })
+    }
+
     pub fn get(&self, name: &str) -> Option<Value> {
         self.m_scope.get(name).cloned().or_else(|| {
             self.m_parent
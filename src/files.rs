@@ -0,0 +1,111 @@
+use crate::environment::Environment;
+use crate::interpreter::{define_native, invalid_argument};
+use crate::value::*;
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+fn path_arg<'a>(function: &str, value: &'a Value) -> Result<&'a Path, String> {
+    value
+        .as_str()
+        .map(Path::new)
+        .ok_or_else(|| invalid_argument(function, "a path", value))
+}
+
+fn io_error(action: &str, path: &Path, err: std::io::Error) -> String {
+    format!("Could not {} => {}: {}", action, path.display(), err)
+}
+
+fn path_string(path: Option<&std::ffi::OsStr>) -> Value {
+    path.map_or(Value::Nil, |path| {
+        Value::String(path.to_string_lossy().to_string())
+    })
+}
+
+/// Paths are relative to the working directory. Anything written is converted as `print` would.
+pub fn define_file_natives(env: &Rc<RefCell<Environment>>) {
+    define_native(env, "read_file", 1, |args| {
+        let path = path_arg("read_file", &args[0])?;
+        fs::read_to_string(path)
+            .map(Value::String)
+            .map_err(|err| io_error("read file", path, err))
+    });
+
+    define_native(env, "write_file", 2, |args| {
+        let path = path_arg("write_file", &args[0])?;
+        fs::write(path, args[1].to_string())
+            .map(|_| Value::Nil)
+            .map_err(|err| io_error("write file", path, err))
+    });
+
+    define_native(env, "append_file", 2, |args| {
+        let path = path_arg("append_file", &args[0])?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(args[1].to_string().as_bytes()))
+            .map(|_| Value::Nil)
+            .map_err(|err| io_error("append to file", path, err))
+    });
+
+    define_native(env, "exists", 1, |args| {
+        Ok(Value::Boolean(path_arg("exists", &args[0])?.exists()))
+    });
+
+    define_native(env, "list_dir", 1, |args| {
+        let path = path_arg("list_dir", &args[0])?;
+        let mut names = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|err| io_error("list directory", path, err))?;
+        names.sort();
+        Ok(Value::List(Rc::new(
+            names.into_iter().map(Value::String).collect(),
+        )))
+    });
+
+    define_native(env, "mkdir", 1, |args| {
+        let path = path_arg("mkdir", &args[0])?;
+        fs::create_dir_all(path)
+            .map(|_| Value::Nil)
+            .map_err(|err| io_error("create directory", path, err))
+    });
+
+    // directories must be empty, so a mistyped path cannot delete a tree
+    define_native(env, "remove", 1, |args| {
+        let path = path_arg("remove", &args[0])?;
+        match path.is_dir() {
+            true => fs::remove_dir(path),
+            false => fs::remove_file(path),
+        }
+        .map(|_| Value::Nil)
+        .map_err(|err| io_error("remove", path, err))
+    });
+
+    define_native(env, "join_path", 2, |args| {
+        let base = path_arg("join_path", &args[0])?;
+        let path = path_arg("join_path", &args[1])?;
+        Ok(Value::String(base.join(path).to_string_lossy().to_string()))
+    });
+
+    define_native(env, "basename", 1, |args| {
+        Ok(path_string(path_arg("basename", &args[0])?.file_name()))
+    });
+
+    define_native(env, "dirname", 1, |args| {
+        Ok(path_string(
+            path_arg("dirname", &args[0])?.parent().map(Path::as_os_str),
+        ))
+    });
+
+    define_native(env, "extension", 1, |args| {
+        Ok(path_string(path_arg("extension", &args[0])?.extension()))
+    });
+}
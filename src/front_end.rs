@@ -0,0 +1,95 @@
+use crate::ast_v2::*;
+use crate::parser;
+use crate::parser_v2;
+pub use crate::parser_v2::ParseError;
+use crate::test_runner::discover;
+
+use anyhow::Result;
+use std::cell::Cell;
+use std::path::Path;
+
+/// The parser that turns source into syntax trees. Both build the same trees and report the same
+/// errors; `Handwritten` is a recursive descent parser over the tokens of `Lexer`, `Nom` a set of
+/// parser combinators working on the source directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum FrontEnd {
+    #[default]
+    Nom,
+    Handwritten,
+}
+
+thread_local! {
+    static FRONT_END: Cell<FrontEnd> = const { Cell::new(FrontEnd::Nom) };
+}
+
+pub fn set_front_end(front_end: FrontEnd) {
+    FRONT_END.with(|cell| cell.set(front_end));
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    match FRONT_END.with(Cell::get) {
+        FrontEnd::Nom => parser_v2::parse_program(input),
+        FrontEnd::Handwritten => parser::parse_program(input),
+    }
+}
+
+fn describe(result: &Result<Vec<Stmt>, Vec<ParseError>>) -> String {
+    match result {
+        Ok(statements) => format!("accepted with {} statement(s)", statements.len()),
+        Err(errors) => errors.iter().fold("rejected with".into(), |out, err| {
+            format!("{}\n    {} ({:?})", out, err, err.get_span())
+        }),
+    }
+}
+
+/// Where the two front ends disagree about `source`, if they do.
+fn compare(source: &str) -> Option<String> {
+    let nom = parser_v2::parse_program(source);
+    let handwritten = parser::parse_program(source);
+
+    match (&nom, &handwritten) {
+        (Ok(nom), Ok(handwritten)) if nom.len() == handwritten.len() => {
+            let (index, (nom, handwritten)) = nom
+                .iter()
+                .zip(handwritten)
+                .enumerate()
+                .find(|(_, (nom, handwritten))| nom != handwritten)?;
+
+            let (nom, handwritten) = (nom.tree(), handwritten.tree());
+            let detail = match nom == handwritten {
+                true => "the trees match but their spans differ\n".into(),
+                false => format!("\nnom:\n{}handwritten:\n{}", nom, handwritten),
+            };
+            Some(format!("statement {} differs: {}", index + 1, detail))
+        }
+        (Err(nom_errors), Err(handwritten_errors)) if nom_errors == handwritten_errors => None,
+        _ => Some(format!(
+            "nom {}\nhandwritten {}",
+            describe(&nom),
+            describe(&handwritten)
+        )),
+    }
+}
+
+/// Parses every script under `path` with both front ends and prints where they disagree, returning
+/// whether they agreed on all of them.
+pub fn compare_front_ends(path: &Path) -> Result<bool> {
+    let (mut agreed, mut total) = (0, 0);
+
+    for script in discover(path)? {
+        let source = std::fs::read_to_string(&script)?;
+        total += 1;
+        match compare(&source) {
+            None => agreed += 1,
+            Some(difference) => {
+                println!("DIFF {}", script.display());
+                difference.lines().for_each(|line| println!("    {}", line));
+            }
+        }
+    }
+
+    println!();
+    println!("{} of {} scripts parse the same", agreed, total);
+
+    Ok(agreed == total)
+}
@@ -0,0 +1,180 @@
+use crate::compat::*;
+use crate::token_v2::{Span, StringLiteral, StringPart};
+
+use std::ops::Range;
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[90m";
+const NATIVE: &str = "\x1b[36m";
+const MATCHING: &str = "\x1b[1;4m";
+const ERROR: &str = "\x1b[1;31m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Identifier,
+    Bracket,
+    Other,
+}
+
+/// Splits source into classified ranges. Unlike the parser it accepts anything, so input that is
+/// still being typed, such as an unterminated string, is classified as far as it goes.
+pub fn classify(source: &str) -> Vec<(Range<usize>, Class)> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    while let Some(c) = source[start..].chars().next() {
+        let rest = &source[start..];
+        // the length of the token starting with `c` and continuing while `pred` holds
+        let run = |pred: fn(char) -> bool| {
+            let first = c.len_utf8();
+            first
+                + rest[first..]
+                    .find(|c| !pred(c))
+                    .unwrap_or(rest.len() - first)
+        };
+
+        if let Some(literal) = StringLiteral::scan(rest) {
+            let end = start + literal.get_end().unwrap_or(rest.len());
+            // an expression embedded with `${...}` is colored as code
+            let mut from = start;
+            for part in literal.get_parts() {
+                if let StringPart::Code(span) = part {
+                    let code = start + span.start()..start + span.end();
+                    tokens.push((from..code.start, Class::String));
+                    tokens.extend(classify(&source[code.clone()]).into_iter().map(
+                        |(range, class)| (code.start + range.start..code.start + range.end, class),
+                    ));
+                    from = code.end;
+                }
+            }
+            tokens.push((from..end, Class::String));
+            start = end;
+            continue;
+        }
+
+        let (len, class) = match c {
+            '/' if rest.starts_with("//") => {
+                (rest.find('\n').unwrap_or(rest.len()), Class::Comment)
+            }
+            c if c.is_ascii_digit() => (run(|c| c.is_ascii_digit() || c == '.'), Class::Number),
+            c if c.is_alphabetic() || c == '_' => {
+                let len = run(|c| c.is_alphanumeric() || c == '_');
+                match is_keyword(&rest[..len]) {
+                    true => (len, Class::Keyword),
+                    false => (len, Class::Identifier),
+                }
+            }
+            '(' | ')' | '{' | '}' => (1, Class::Bracket),
+            c => (c.len_utf8(), Class::Other),
+        };
+
+        tokens.push((start..start + len, class));
+        start += len;
+    }
+
+    tokens
+}
+
+/// The offset of the bracket matching the one at `pos`, or just before it, ignoring brackets
+/// inside strings and comments.
+pub fn matching_bracket(source: &str, pos: usize) -> Option<usize> {
+    let mut open = Vec::new();
+    let mut pairs = Vec::new();
+    for (range, class) in classify(source) {
+        match (class, &source[range.clone()]) {
+            (Class::Bracket, "(" | "{") => open.push(range.start),
+            (Class::Bracket, close) => {
+                let start = open.pop();
+                if let Some(start) = start.filter(|&start| {
+                    matches!((&source[start..start + 1], close), ("(", ")") | ("{", "}"))
+                }) {
+                    pairs.push((start, range.start));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    [Some(pos), pos.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find_map(|at| {
+            pairs.iter().find_map(|&(open, close)| match at {
+                at if at == open => Some(close),
+                at if at == close => Some(open),
+                _ => None,
+            })
+        })
+}
+
+/// Colors `source` for display, marking the bracket that matches the one at the cursor.
+/// `is_native` tells whether an identifier names a native function.
+pub fn highlight(source: &str, cursor: usize, is_native: impl Fn(&str) -> bool) -> String {
+    let matching = matching_bracket(source, cursor);
+    let mut out = String::with_capacity(source.len() * 2);
+
+    for (range, class) in classify(source) {
+        let text = &source[range.clone()];
+        let color = match class {
+            Class::Keyword => KEYWORD,
+            Class::String => STRING,
+            Class::Number => NUMBER,
+            Class::Comment => COMMENT,
+            Class::Identifier if is_native(text) => NATIVE,
+            Class::Bracket if matching == Some(range.start) => MATCHING,
+            _ => {
+                out.push_str(text);
+                continue;
+            }
+        };
+        out.push_str(color);
+        out.push_str(text);
+        out.push_str(RESET);
+    }
+
+    out
+}
+
+/// Renders an error in color, without quoting the source.
+pub fn error_message(message: &str) -> String {
+    format!("{}error{}: {}\n", ERROR, RESET, message)
+}
+
+/// Renders an error in color, quoting each line of `source` that `span` covers with the covered
+/// part underlined. An error at the end of the source points just past its last line.
+pub fn underline(source: &str, span: Span, message: &str) -> String {
+    let mut out = error_message(message);
+    let source = source.trim_end();
+    let start = span.start().min(source.len());
+    let end = span.end().clamp(start + 1, source.len() + 1);
+
+    let mut line_start = 0;
+    for line in source.split('\n') {
+        let line_end = line_start + line.len();
+        if line_start < end && start <= line_end {
+            let from = start.saturating_sub(line_start);
+            let to = (end - line_start).min(line.len()).max(from + 1);
+            let width = line
+                .get(from..to)
+                .map_or(1, |text| text.chars().count().max(1));
+            out.push_str(&format!(
+                "  {}\n  {}{}{}{}\n",
+                line,
+                " ".repeat(line[..from].chars().count()),
+                ERROR,
+                "^".repeat(width),
+                RESET
+            ));
+        }
+        line_start = line_end + 1;
+    }
+
+    out
+}
@@ -0,0 +1,92 @@
+use crate::ast_v2::Stmt;
+use crate::environment::Environment;
+use crate::module::module_source;
+use crate::token_v2::Span;
+use crate::value::{Callable, Value};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Callbacks made by the evaluator while it runs a program, letting tools such as debuggers,
+/// tracers and profilers follow execution. Every callback does nothing by default.
+pub trait ExecutionHook {
+    /// Called before a statement runs, with the environment it runs in.
+    fn on_statement(&mut self, _stmt: &Stmt, _env: &Rc<RefCell<Environment>>) {}
+
+    /// Called when a Lox function is entered, after its arguments are evaluated.
+    fn on_call(&mut self, _callable: &Callable, _arguments: &[Value]) {}
+
+    /// Called when a Lox function returns, normally or with an error.
+    fn on_return(&mut self, _callable: &Callable, _result: &Result<Value, Vec<String>>) {}
+
+    /// Called when a conditional picks a path. For `if` and `while` the span is the condition and
+    /// `taken` is whether it held. For `and` and `or` the span is the operator and `taken` is
+    /// whether the right operand is evaluated. `env` is the environment the condition runs in.
+    fn on_branch(&mut self, _span: Span, _taken: bool, _env: &Rc<RefCell<Environment>>) {}
+
+    /// Called when a variable or function is declared.
+    fn on_define(&mut self, _name: &str, _value: &Value) {}
+
+    /// Called when an existing variable is assigned a new value.
+    fn on_assign(&mut self, _name: &str, _value: &Value) {}
+
+    /// Called where a runtime error is raised, before it unwinds the call stack.
+    fn on_error(&mut self, _message: &str) {}
+
+    /// Whether the program should stop, as when the user quits a debugger. Once it is, no more
+    /// statements run and loops end.
+    fn is_stopped(&self) -> bool {
+        false
+    }
+}
+
+pub type Hook = Option<Rc<RefCell<dyn ExecutionHook>>>;
+
+/// The sources the spans handed to a hook point into: the main script's, and each imported
+/// module's by path. Tools keep one to turn spans into lines.
+#[derive(Debug)]
+pub struct Sources {
+    m_main: Rc<str>,
+    m_modules: HashMap<PathBuf, Rc<str>>,
+}
+
+impl Sources {
+    pub fn new(main: String) -> Sources {
+        Sources {
+            m_main: main.into(),
+            m_modules: HashMap::new(),
+        }
+    }
+
+    pub fn get_main(&self) -> &str {
+        &self.m_main
+    }
+
+    /// The source of `module`, or of the main script for `None`. A module's source is kept once
+    /// it has been asked for, so reports can still be made after the modules are cleared.
+    pub fn get(&mut self, module: Option<&Path>) -> Rc<str> {
+        let Some(path) = module else {
+            return self.m_main.clone();
+        };
+
+        if let Some(source) = self.m_modules.get(path) {
+            return source.clone();
+        }
+        let source = module_source(path).unwrap_or_default();
+        self.m_modules.insert(path.to_path_buf(), source.clone());
+        source
+    }
+
+    /// The modules looked up so far, sorted by path.
+    pub fn get_modules(&self) -> Vec<(&Path, &str)> {
+        let mut modules = self
+            .m_modules
+            .iter()
+            .map(|(path, source)| (path.as_path(), &**source))
+            .collect::<Vec<_>>();
+        modules.sort();
+        modules
+    }
+}
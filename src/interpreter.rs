@@ -1,5 +1,14 @@
+use crate::ast_v2::{Expr, Stmt};
 use crate::environment::*;
-use crate::parser_v2::*;
+use crate::files::*;
+use crate::front_end::*;
+use crate::highlight::*;
+use crate::hook::*;
+use crate::math::*;
+use crate::module::*;
+use crate::strings::*;
+use crate::system::*;
+use crate::token_v2::Span;
 // use crate::lexer::*;
 // use crate::parser::*;
 
@@ -8,214 +17,485 @@ use crate::visitor::*;
 
 use rand::Rng;
 use std::cell::RefCell;
-use std::io::Write;
+use std::io::{BufRead, Write};
+use std::path::Path;
 use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+/// The start of every error raised by `assert`, `assert_eq` and `assert_ne`, which tells failed
+/// tests apart from tests that stopped on some other runtime error.
+pub const ASSERTION_FAILED: &str = "Assertion failed";
+
+thread_local! {
+    static OUTPUT: RefCell<Option<Box<dyn Write>>> = RefCell::new(None);
+    static INPUT: RefCell<Option<Box<dyn BufRead>>> = RefCell::new(None);
+    static TESTS: RefCell<Vec<(String, Callable)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes program output to stdout, or wherever the host redirected it with
+/// `Interpreter::set_output`.
+pub fn write_output(text: &str) {
+    OUTPUT.with(|output| match output.borrow_mut().as_mut() {
+        Some(output) => {
+            output.write_all(text.as_bytes()).unwrap();
+            output.flush().unwrap();
+        }
+        None => {
+            print!("{}", text);
+            std::io::stdout().flush().unwrap();
+        }
+    });
+}
+
+/// Reads a line of program input from stdin, or from wherever the host redirected it with
+/// `Interpreter::set_input`.
+fn read_input(input: &mut String) -> std::io::Result<usize> {
+    INPUT.with(|cell| match cell.borrow_mut().as_mut() {
+        Some(reader) => reader.read_line(input),
+        None => std::io::stdin().read_line(input),
+    })
+}
+
+/// Hands over the tests registered with `test(name, fun)` since the last call, in the order
+/// they were registered.
+pub fn take_tests() -> Vec<(String, Callable)> {
+    TESTS.with(|tests| tests.take())
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Parse(Vec<ParseError>),
+    Runtime(Vec<String>),
+}
+
+impl RunError {
+    /// The status a script exits with, following the sysexits codes clox uses.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Parse(_) => 65,
+            RunError::Runtime(_) => 70,
+        }
+    }
+}
+
+/// Defines the native function `name`, taking `arity` arguments, in `env`.
+pub fn define_native(env: &Rc<RefCell<Environment>>, name: &str, arity: usize, function: NativeFn) {
+    env.borrow_mut().define(
+        name.into(),
+        Value::Callable(Callable::NativeFunction(None, arity, Box::new(function))),
+    );
+}
+
+/// The runtime error raised when a native function is called with an argument it cannot use.
+pub fn invalid_argument(function: &str, expected: &str, value: &Value) -> String {
+    format!(
+        "Invalid argument => {} expects {}, got {:?}",
+        function, expected, value
+    )
+}
+
+/// Defines the native functions in `env`, the outermost scope of a script or module.
+pub fn define_natives(env: &Rc<RefCell<Environment>>) {
+    env.borrow_mut().define(
+        "clock".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            0,
+            Box::new(|_| {
+                Ok(Value::Number(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs_f64(),
+                ))
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "sleep_secs".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            1,
+            Box::new(|args| {
+                let secs = number_arg("sleep_secs", &args[0])?;
+                let duration = std::time::Duration::try_from_secs_f64(secs)
+                    .map_err(|_| invalid_argument("sleep_secs", "a duration", &args[0]))?;
+                std::thread::sleep(duration);
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "sleep_millis".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            1,
+            Box::new(|args| {
+                let millis = number_arg("sleep_millis", &args[0])?;
+                let duration = std::time::Duration::try_from_secs_f64(millis / 1000.0)
+                    .map_err(|_| invalid_argument("sleep_millis", "a duration", &args[0]))?;
+                std::thread::sleep(duration);
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "print".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            1,
+            Box::new(|args| {
+                write_output(&format!("{}", args[0]));
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "println".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            1,
+            Box::new(|args| {
+                write_output(&format!("{}\n", args[0]));
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "read_line".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            0,
+            Box::new(|_| {
+                let mut input = String::new();
+                read_input(&mut input).unwrap();
+                Ok(Value::String(input.trim_end().into()))
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "dbg".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| {
+                write_output(&format!("{} => {:?}\n\n", args[0], args[1]));
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "rand".to_string(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            0,
+            Box::new(|_| Ok(Value::Number(rand::random::<f64>()))),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "rand_range".to_string(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| {
+                let mut rng = rand::thread_rng();
+                let min = number_arg("rand_range", &args[0])?;
+                let max = number_arg("rand_range", &args[1])?;
+                match min < max && (max - min).is_finite() {
+                    true => Ok(Value::Number(rng.gen_range(min..max))),
+                    false => Err(format!("Invalid range => {}..{}", min, max)),
+                }
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "rand_int".to_string(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            0,
+            Box::new(|_| Ok(Value::Number(rand::random::<i32>() as f64))),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "rand_int_range".to_string(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| {
+                let mut rng = rand::thread_rng();
+                let min = number_arg("rand_int_range", &args[0])? as i32;
+                let max = number_arg("rand_int_range", &args[1])? as i32;
+                match min < max {
+                    true => Ok(Value::Number(rng.gen_range(min..max) as f64)),
+                    false => Err(format!("Invalid range => {}..{}", min, max)),
+                }
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "test0".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            0,
+            Box::new(|_| {
+                write_output("testing123 from native print function\n");
+                Ok(Value::Nil)
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "assert".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            1,
+            Box::new(|args| match args[0].is_truthy() {
+                true => Ok(Value::Nil),
+                false => Err(format!("{} => {:?}", ASSERTION_FAILED, args[0])),
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "assert_eq".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| match args[0].is_equal(&args[1]) {
+                true => Ok(Value::Nil),
+                false => Err(format!(
+                    "{} => left: {:?}, right: {:?}",
+                    ASSERTION_FAILED, args[0], args[1]
+                )),
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "assert_ne".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| match args[0].is_equal(&args[1]) {
+                true => Err(format!(
+                    "{} => left: {:?}, right: {:?}",
+                    ASSERTION_FAILED, args[0], args[1]
+                )),
+                false => Ok(Value::Nil),
+            }),
+        )),
+    );
+
+    env.borrow_mut().define(
+        "test".into(),
+        Value::Callable(Callable::NativeFunction(
+            None,
+            2,
+            Box::new(|args| match (&args[0], &args[1]) {
+                (Value::String(name), Value::Callable(callable)) if callable.arity() == 0 => {
+                    TESTS.with(|tests| tests.borrow_mut().push((name.clone(), callable.clone())));
+                    Ok(Value::Nil)
+                }
+                (name, callable) => Err(format!(
+                    "Invalid test registration => {:?}, {:?}",
+                    name, callable
+                )),
+            }),
+        )),
+    );
+
+    define_string_natives(env);
+    define_math_natives(env);
+    define_file_natives(env);
+    define_system_natives(env);
+}
+
+#[derive(Clone)]
 pub struct Interpreter {
     m_environment: Rc<RefCell<Environment>>,
+    m_hook: Hook,
+    m_echo: bool,
+    m_color: bool,
+    // where the last runtime error came from, in the source being run
+    m_error_span: Option<Span>,
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
         let global_env = Environment::new();
+        define_natives(&global_env);
 
-        global_env.borrow_mut().define(
-            "clock".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                0,
-                Box::new(|_| {
-                    Value::Number(
-                        std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs_f64(),
-                    )
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "sleep_secs".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                1,
-                Box::new(|args| {
-                    std::thread::sleep(std::time::Duration::from_secs_f64(
-                        args[0].as_number().unwrap(),
-                    ));
-                    Value::Nil
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "sleep_millis".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                1,
-                Box::new(|args| {
-                    std::thread::sleep(std::time::Duration::from_millis(
-                        args[0].as_number().unwrap() as u64,
-                    ));
-                    Value::Nil
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "print".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                1,
-                Box::new(|args| {
-                    print!("{}", args[0]);
-                    std::io::stdout().flush().unwrap();
-                    Value::Nil
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "println".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                1,
-                Box::new(|args| {
-                    println!("{}", args[0]);
-                    Value::Nil
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "read_line".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                0,
-                Box::new(|_| {
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input).unwrap();
-                    Value::String(input.trim_end().into())
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "parse".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                1,
-                Box::new(|args| match args[0] {
-                    Value::String(ref string) => {
-                        let result = string.parse::<f64>();
-                        match result {
-                            Ok(number) => Value::Number(number),
-                            Err(_) => Value::Nil,
-                        }
-                    }
-                    _ => Value::Nil,
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "dbg".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                2,
-                Box::new(|args| {
-                    println!("{} => {:?}\n", args[0], args[1]);
-                    Value::Nil
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "rand".to_string(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                0,
-                Box::new(|_| Value::Number(rand::random::<f64>())),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "rand_range".to_string(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                2,
-                Box::new(|args| {
-                    let mut rng = rand::thread_rng();
-                    let min = args[0].as_number().unwrap();
-                    let max = args[1].as_number().unwrap();
-                    Value::Number(rng.gen_range(min..max))
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "rand_int".to_string(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                0,
-                Box::new(|_| Value::Number(rand::random::<i32>() as f64)),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "rand_int_range".to_string(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                2,
-                Box::new(|args| {
-                    let mut rng = rand::thread_rng();
-                    let min = args[0].as_number().unwrap() as i32;
-                    let max = args[1].as_number().unwrap() as i32;
-                    Value::Number(rng.gen_range(min..max) as f64)
-                }),
-            )),
-        );
-
-        global_env.borrow_mut().define(
-            "test0".into(),
-            Value::Callable(Callable::NativeFunction(
-                None,
-                0,
-                Box::new(|_| {
-                    println!("testing123 from native print function");
-                    Value::Nil
-                }),
-            )),
-        );
+        // tests and modules loaded by an earlier interpreter on this thread do not belong to
+        // this one
+        TESTS.with(|tests| tests.borrow_mut().clear());
+        clear_modules();
+        set_script_args(Vec::new());
 
         Interpreter {
             m_environment: global_env,
+            m_hook: None,
+            m_echo: false,
+            m_color: false,
+            m_error_span: None,
         }
     }
 
-    pub fn interpret(&mut self, input: String) {
-        if let Ok(stmts) = parse_program(&input) {
+    /// Names the file the program comes from, which imports are resolved against.
+    pub fn set_path(&mut self, path: &Path) {
+        self.m_environment.borrow_mut().set_path(path);
+    }
+
+    /// Runs `source` as if it came from the file at `path`, so that its imports are resolved
+    /// against that file, then goes back to the previous path.
+    pub fn interpret_file(&mut self, source: String, path: &Path) -> Result<(), RunError> {
+        let previous = self
+            .m_environment
+            .borrow_mut()
+            .replace_path(Some(path.to_path_buf()));
+        let result = self.interpret(source);
+        self.m_environment.borrow_mut().replace_path(previous);
+        result
+    }
+
+    /// Passes `args` to the script, and the modules it imports, as the `args` list.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        set_script_args(args);
+        self.m_environment
+            .borrow_mut()
+            .define("args".into(), script_args());
+    }
+
+    pub fn get_environment(&self) -> Rc<RefCell<Environment>> {
+        self.m_environment.clone()
+    }
+
+    /// Makes `interpret` print the value of each top-level expression statement as `=> value`,
+    /// leaving out `nil` so calls like `println(x)` stay quiet.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.m_echo = echo;
+    }
+
+    /// Makes `interpret` report errors in color, with the code they come from underlined.
+    pub fn set_color(&mut self, color: bool) {
+        self.m_color = color;
+    }
+
+    pub fn set_hook(&mut self, hook: Rc<RefCell<dyn ExecutionHook>>) {
+        self.m_hook = Some(hook);
+    }
+
+    /// Sends program output, including runtime errors, to `output` instead of stdout. The
+    /// redirect applies to every interpreter on the current thread.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        OUTPUT.with(|cell| *cell.borrow_mut() = Some(output));
+    }
+
+    /// Reads the program's input, as with `read_line`, from `input` instead of stdin. Like the
+    /// output, the redirect applies to every interpreter on the current thread.
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        INPUT.with(|cell| *cell.borrow_mut() = Some(input));
+    }
+
+    /// Runs a program, printing each error as it happens and carrying on with the next
+    /// statement after a runtime error. Returns every error that was printed.
+    pub fn interpret(&mut self, input: String) -> Result<(), RunError> {
+        match parse_program(&input) {
+            Ok(stmts) => {
+                let mut errors = Vec::new();
                 for stmt in stmts {
-                    let mut visitor = StmtEvaluator::new(&self.m_environment);
-                    stmt.accept(&mut visitor);
-
-                    match visitor.get_result() {
-                        Ok(_) => {}
-                        Err(err) => {
-                            println!(
-                                "Runtime produced {} {}:",
-                                err.len(),
-                                if err.len() == 1 { "error" } else { "errors" }
-                            );
-                            err.iter().for_each(|err| println!("    ERROR: {}", &err));
+                    let result = match (&stmt, self.m_echo) {
+                        (Stmt::Expression { m_expression }, true) => {
+                            self.evaluate(m_expression).map(|value| {
+                                if !matches!(value, Value::Nil) {
+                                    write_output(&format!("=> {:?}\n", value));
+                                }
+                            })
                         }
+                        _ => self.execute(&stmt),
+                    };
+
+                    if let Err(err) = result {
+                        if self.m_color {
+                            err.iter().for_each(|err| match self.m_error_span {
+                                Some(span) => write_output(&underline(&input, span, err)),
+                                None => write_output(&error_message(err)),
+                            });
+                            errors.extend(err);
+                            continue;
+                        }
+
+                        write_output(&format!(
+                            "Runtime produced {} {}:\n",
+                            err.len(),
+                            if err.len() == 1 { "error" } else { "errors" }
+                        ));
+                        err.iter()
+                            .for_each(|err| write_output(&format!("    ERROR: {}\n", &err)));
+                        errors.extend(err);
                     }
                 }
+
+                match errors.is_empty() {
+                    true => Ok(()),
+                    false => Err(RunError::Runtime(errors)),
+                }
+            }
+            Err(err) if self.m_color => {
+                err.iter().for_each(|err| {
+                    write_output(&underline(&input, err.get_span(), err.get_message()));
+                });
+                Err(RunError::Parse(err))
+            }
+            Err(err) => {
+                write_output(&format!(
+                    "Parser produced {} {}:\n",
+                    err.len(),
+                    if err.len() == 1 { "error" } else { "errors" }
+                ));
+                err.iter()
+                    .for_each(|err| write_output(&format!("    ERROR: {}\n", err)));
+                Err(RunError::Parse(err))
+            }
         }
     }
 
+    /// Runs a program like `interpret`, but stops at the first statement that fails and returns
+    /// its errors instead of printing them.
+    pub fn run(&mut self, input: &str) -> Result<(), RunError> {
+        let stmts = parse_program(input).map_err(RunError::Parse)?;
+        for stmt in &stmts {
+            self.execute(stmt).map_err(RunError::Runtime)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, Vec<String>> {
+        let mut visitor = ExprEvaluator::new(&self.m_environment, &self.m_hook);
+        expr.accept(&mut visitor);
+        self.m_error_span = visitor.get_error_span();
+        visitor.get_result()
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Vec<String>> {
+        let mut visitor = StmtEvaluator::new(&self.m_environment, &self.m_hook);
+        visitor.execute(stmt);
+        self.m_error_span = visitor.get_error_span();
+        visitor
+            .get_result()
+            .map_err(|err| err.iter().map(|err| err.to_string()).collect())
+    }
+
     // pub fn interpret(&mut self, input: String) {
     //     match Lexer::new(&input).tokenize() {
     //         Ok(tokens) => match Parser::new(tokens).parse() {
@@ -1,318 +1,163 @@
-use crate::token::*;
+use crate::token_v2::*;
 
-use anyhow::Result;
-use itertools::*;
+use std::iter::Peekable;
+use std::str::CharIndices;
 
+/// Splits source into the tokens the hand-written parser reads, with the same spans the nom
+/// parser gives them. Comments are kept, since they become statements, and a mistake becomes an
+/// error token for the parser to report where the nom parser would.
 #[derive(Debug)]
 pub struct Lexer<'a> {
-    m_chars: std::iter::Peekable<std::str::CharIndices<'a>>,
-    m_line_number: usize,
-    m_last_line_index: usize,
+    m_source: &'a str,
+    m_chars: Peekable<CharIndices<'a>>,
     m_tokens: Vec<Token>,
-    m_errors: Vec<String>,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self {
+        let mut lexer = Self {
+            m_source: input,
             m_chars: input.char_indices().peekable(),
-            m_line_number: 1,
-            m_last_line_index: 0,
             m_tokens: Vec::new(),
-            m_errors: Vec::new(),
+        };
+        // a `#!` line is skipped, leaving its newline to count
+        if input.starts_with("#!") {
+            lexer.take_while(|c| c != '\n');
         }
+        lexer
     }
 
-    fn lex_token(&mut self) -> Result<Token, String> {
-        return match self.m_chars.next() {
-            Some((index, char)) => match char {
-                '\n' => {
-                    self.m_line_number += 1;
-                    self.m_last_line_index = index;
-                    Ok(Token::new_token(
-                        TokenType::Skip,
-                        index,
-                        1,
-                        self.m_line_number,
-                    ))
-                }
-                ' ' | '\r' | '\t' => Ok(Token::new_token(
-                    TokenType::Skip,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '(' => Ok(Token::new_token(
-                    TokenType::LeftParen,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                ')' => Ok(Token::new_token(
-                    TokenType::RightParen,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '{' => Ok(Token::new_token(
-                    TokenType::LeftBrace,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '}' => Ok(Token::new_token(
-                    TokenType::RightBrace,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                ',' => Ok(Token::new_token(
-                    TokenType::Comma,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '.' => Ok(Token::new_token(
-                    TokenType::Dot,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '-' => Ok(Token::new_token(
-                    TokenType::Minus,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '+' => Ok(Token::new_token(
-                    TokenType::Plus,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                ';' => Ok(Token::new_token(
-                    TokenType::Semicolon,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '*' => Ok(Token::new_token(
-                    TokenType::Star,
-                    index - self.m_last_line_index,
-                    1,
-                    self.m_line_number,
-                )),
-                '!' => match self.m_chars.peek() {
-                    Some((_, '=')) => {
-                        self.m_chars.next();
-                        Ok(Token::new_token(
-                            TokenType::BangEqual,
-                            index - self.m_last_line_index,
-                            2,
-                            self.m_line_number,
-                        ))
-                    }
-                    _ => Ok(Token::new_token(
-                        TokenType::Bang,
-                        index - self.m_last_line_index,
-                        1,
-                        self.m_line_number,
-                    )),
-                },
-                '=' => match self.m_chars.peek() {
-                    Some((_, '=')) => {
-                        self.m_chars.next();
-                        Ok(Token::new_token(
-                            TokenType::EqualEqual,
-                            index - self.m_last_line_index,
-                            2,
-                            self.m_line_number,
-                        ))
-                    }
-                    _ => Ok(Token::new_token(
-                        TokenType::Equal,
-                        index,
-                        1,
-                        self.m_line_number,
-                    )),
-                },
-                '<' => match self.m_chars.peek() {
-                    Some((_, '=')) => {
-                        self.m_chars.next();
-                        Ok(Token::new_token(
-                            TokenType::LessEqual,
-                            index - self.m_last_line_index,
-                            2,
-                            self.m_line_number,
-                        ))
-                    }
-                    _ => Ok(Token::new_token(
-                        TokenType::Less,
-                        index - self.m_last_line_index,
-                        1,
-                        self.m_line_number,
-                    )),
-                },
-                '>' => match self.m_chars.peek() {
-                    Some((_, '=')) => {
-                        self.m_chars.next();
-                        Ok(Token::new_token(
-                            TokenType::GreaterEqual,
-                            index - self.m_last_line_index,
-                            2,
-                            self.m_line_number,
-                        ))
-                    }
-                    _ => Ok(Token::new_token(
-                        TokenType::Greater,
-                        index - self.m_last_line_index,
-                        1,
-                        self.m_line_number,
-                    )),
-                },
-                '/' => match self.m_chars.peek() {
-                    Some((_, '/')) => {
-                        self.m_chars.next();
-                        for (_, char) in self.m_chars.by_ref() {
-                            if char == '\n' {
-                                self.m_line_number += 1;
-                                self.m_last_line_index = index;
-                                break;
-                            }
-                        }
-                        Ok(Token::new_token(
-                            TokenType::Skip,
-                            index - self.m_last_line_index,
-                            1,
-                            self.m_line_number,
-                        ))
-                    }
-                    _ => Ok(Token::new_token(
-                        TokenType::Slash,
-                        index - self.m_last_line_index,
-                        1,
-                        self.m_line_number,
-                    )),
-                },
-                '"' => {
-                    let mut lexeme = String::new();
-                    loop {
-                        match self.m_chars.next() {
-                            Some((_, '"')) => break,
-                            Some((_, '\n')) => {
-                                self.m_line_number += 1;
-                                self.m_last_line_index = index;
-                            }
-                            Some((_, char)) => lexeme.push(char),
-                            None => {
-                                return Err(format!(
-                                    "Unterminated string \"{}\"\n           => line {} | column {}",
-                                    lexeme,
-                                    self.m_line_number,
-                                    index - self.m_last_line_index + 1 + lexeme.len()
-                                ))
-                            }
-                        }
-                    }
+    /// A lexer for the part of `input` in `span`, such as an expression embedded in a string,
+    /// whose tokens have spans within the whole of `input`.
+    pub fn new_within(input: &'a str, span: Span) -> Self {
+        let mut chars = input[..span.end()].char_indices().peekable();
+        while chars.next_if(|&(index, _)| index < span.start()).is_some() {}
+        Self {
+            m_source: &input[..span.end()],
+            m_chars: chars,
+            m_tokens: Vec::new(),
+        }
+    }
 
-                    Ok(Token::new_token(
-                        TokenType::String(lexeme.clone()),
-                        index - self.m_last_line_index,
-                        lexeme.len() + 2,
-                        self.m_line_number,
-                    ))
-                }
-                char if char.is_ascii_digit() => {
-                    let mut lexeme = String::new();
-                    lexeme.push(char);
+    /// An error token for the malformed token from `start`, whose span ends where the mistake
+    /// is: the first character from `index` that is not a space or tab.
+    fn error(&self, message: &str, start: usize, index: usize) -> Option<Token> {
+        let rest = self.m_source[index..].trim_start_matches([' ', '\t']);
+        Some(Token::new_token(
+            TokenType::Error(message.into()),
+            Span::new(start, self.m_source.len() - rest.len()),
+        ))
+    }
 
-                    while let Some((_, char)) = self.m_chars.peek() {
-                        if char.is_ascii_digit() {
-                            lexeme.push(*char);
-                            self.m_chars.next();
-                        } else {
-                            break;
-                        }
-                    }
+    /// Consumes characters while `pred` holds, returning the offset just past the last one.
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> usize {
+        while let Some(&(_, c)) = self.m_chars.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.m_chars.next();
+        }
 
-                    if let Some((_, '.')) = self.m_chars.peek() {
-                        if let Some(past_point) = self.m_chars.clone().multipeek().nth(1) {
-                            if past_point.1.is_ascii_digit() {
-                                lexeme.push('.');
-                                self.m_chars.next();
+        self.m_chars
+            .peek()
+            .map_or(self.m_source.len(), |&(index, _)| index)
+    }
 
-                                while let Some((_, char)) = self.m_chars.peek() {
-                                    if char.is_ascii_digit() {
-                                        lexeme.push(*char);
-                                        self.m_chars.next();
-                                    } else {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
+    /// Consumes the second character of a two-character operator when it follows.
+    fn either(&mut self, second: char, pair: TokenType, single: TokenType) -> (TokenType, usize) {
+        match self.m_chars.next_if(|&(_, c)| c == second) {
+            Some(_) => (pair, 2),
+            None => (single, 1),
+        }
+    }
 
-                    let parsed_float: f64 = lexeme.parse().unwrap();
+    /// Takes the string literal starting at `index`. Its escape sequences are checked by the
+    /// parser, which reports mistakes in them where the nom parser would.
+    fn lex_string(&mut self, index: usize, literal: StringLiteral) -> Option<Token> {
+        let Some(end) = literal.get_end() else {
+            self.m_chars.by_ref().for_each(drop);
+            return self.error(
+                "Unterminated string",
+                index,
+                index + literal.get_body_start(),
+            );
+        };
 
-                    Ok(Token::new_token(
-                        TokenType::Number(parsed_float),
-                        index - self.m_last_line_index,
-                        lexeme.len(),
-                        self.m_line_number,
-                    ))
-                }
-                char if char.is_ascii_alphabetic() || char == '_' => {
-                    let mut lexeme = String::new();
-                    lexeme.push(char);
+        while self.m_chars.next_if(|&(i, _)| i < index + end).is_some() {}
+        Some(Token::new_token(
+            TokenType::String(literal.get_value().into()),
+            Span::new(index, index + end),
+        ))
+    }
 
-                    while let Some((_, char)) = self.m_chars.peek() {
-                        if char.is_ascii_alphanumeric() || char == &'_' {
-                            lexeme.push(*char);
-                            self.m_chars.next();
-                        } else {
-                            break;
-                        }
-                    }
+    /// Lexes the token starting at `index`, or returns `None` for whitespace.
+    fn lex_token(&mut self, index: usize, char: char) -> Option<Token> {
+        if let Some(literal) = StringLiteral::scan(&self.m_source[index..]) {
+            return self.lex_string(index, literal);
+        }
 
-                    Ok(Token::new_token(
-                        TokenType::new_identifier(lexeme.clone()),
-                        index - self.m_last_line_index,
-                        lexeme.len(),
-                        self.m_line_number,
-                    ))
+        let (token_type, end) = match char {
+            ' ' | '\t' | '\r' | '\n' => return None,
+            '(' => (TokenType::LeftParen, index + 1),
+            ')' => (TokenType::RightParen, index + 1),
+            '{' => (TokenType::LeftBrace, index + 1),
+            '}' => (TokenType::RightBrace, index + 1),
+            ',' => (TokenType::Comma, index + 1),
+            '.' => (TokenType::Dot, index + 1),
+            '-' => (TokenType::Minus, index + 1),
+            '+' => (TokenType::Plus, index + 1),
+            ';' => (TokenType::Semicolon, index + 1),
+            '*' => (TokenType::Star, index + 1),
+            '!' | '=' | '<' | '>' => {
+                let (token_type, len) = match char {
+                    '!' => self.either('=', TokenType::BangEqual, TokenType::Bang),
+                    '=' => self.either('=', TokenType::EqualEqual, TokenType::Equal),
+                    '<' => self.either('=', TokenType::LessEqual, TokenType::Less),
+                    _ => self.either('=', TokenType::GreaterEqual, TokenType::Greater),
+                };
+                (token_type, index + len)
+            }
+            '/' if self.m_chars.next_if(|&(_, c)| c == '/').is_some() => {
+                // the span takes in the newline, as the nom parser's does
+                let end = self.take_while(|c| c != '\n');
+                let end = match self.m_chars.next() {
+                    Some(_) => end + 1,
+                    None => end,
+                };
+                (TokenType::Comment, end)
+            }
+            '/' => (TokenType::Slash, index + 1),
+            char if char.is_ascii_digit() => {
+                let mut end = self.take_while(|c| c.is_ascii_digit());
+                if self.m_chars.next_if(|&(_, c)| c == '.').is_some() {
+                    if !self.m_chars.peek().is_some_and(|(_, c)| c.is_ascii_digit()) {
+                        return self.error("Expected digits after '.'", index, end + 1);
+                    }
+                    end = self.take_while(|c| c.is_ascii_digit());
                 }
-                bad_char => Err(format!(
-                    "Unexpected character '{}'\n           => line {} | column {}",
-                    bad_char,
-                    self.m_line_number,
-                    index + 1
-                )),
-            },
-            None => Err("Unexpected end of input".to_string()),
+                let number = self.m_source[index..end].parse().unwrap();
+                (TokenType::Number(number), end)
+            }
+            char if char.is_alphabetic() || char == '_' => {
+                let end = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                (TokenType::new_identifier(&self.m_source[index..end]), end)
+            }
+            char => (TokenType::Unexpected(char), index + char.len_utf8()),
         };
+
+        Some(Token::new_token(token_type, Span::new(index, end)))
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, Vec<String>> {
-        while self.m_chars.peek().is_some() {
-            match self.lex_token() {
-                Ok(token) if token.get_token_type() == &TokenType::Skip => {}
-                Ok(token) => self.m_tokens.push(token),
-                Err(err) => self.m_errors.push(err),
+    pub fn tokenize(mut self) -> Vec<Token> {
+        while let Some((index, char)) = self.m_chars.next() {
+            if let Some(token) = self.lex_token(index, char) {
+                self.m_tokens.push(token);
             }
         }
 
-        if self.m_errors.is_empty() {
-            self.m_tokens.push(Token::new_token(
-                TokenType::Eof,
-                0,
-                0,
-                self.m_line_number + 1,
-            ));
-
-            Ok(self.m_tokens.clone())
-        } else {
-            Err(self.m_errors.clone())
-        }
+        let end = self.m_source.len();
+        self.m_tokens
+            .push(Token::new_token(TokenType::Eof, Span::new(end, end)));
+        self.m_tokens
     }
 }
@@ -0,0 +1,354 @@
+use crate::compat::keywords;
+use crate::front_end::*;
+use crate::resolver::*;
+use crate::token_v2::*;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// A language server speaking JSON-RPC with `Content-Length` framing, as described by the
+/// Language Server Protocol. Documents are synced in full on every change.
+#[derive(Debug, Default)]
+pub struct LanguageServer {
+    m_documents: HashMap<String, String>,
+    m_shutdown: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> LanguageServer {
+        LanguageServer::default()
+    }
+
+    /// Serves requests until the client sends `exit`. Returns whether the client asked for a
+    /// shutdown first, which decides the exit code of the server.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+        while let Some(message) = read_message(&mut input)? {
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            let params = &message["params"];
+
+            if method == "exit" {
+                break;
+            }
+
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.handle_request(&method, params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(err) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": METHOD_NOT_FOUND, "message": err.to_string() },
+                        }),
+                    };
+                    write_message(&mut output, &response)?;
+                }
+                None => {
+                    for notification in self.handle_notification(&method, params) {
+                        write_message(&mut output, &notification)?;
+                    }
+                }
+            }
+        }
+
+        Ok(self.m_shutdown)
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Json) -> Result<Json> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "rlox", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.m_shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/references" => Ok(self.references(params)),
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            method => Err(anyhow!("Unhandled method => {}", method)),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.m_documents.insert(uri.clone(), text.to_string());
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.m_documents.insert(uri.clone(), text.to_string());
+                }
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didClose" => {
+                self.m_documents.remove(&uri);
+                vec![publish_diagnostics(&uri, vec![])]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn diagnostics(&self, uri: &str) -> Json {
+        let text = self.m_documents.get(uri).map_or("", String::as_str);
+
+        let diagnostics = match parse_program(text) {
+            Ok(stmts) => Resolver::resolve(&stmts)
+                .get_errors()
+                .iter()
+                .map(|err| diagnostic(text, err.get_span(), &err.to_string()))
+                .collect(),
+            Err(errs) => errs
+                .iter()
+                .map(|err| diagnostic(text, err.get_span(), err.get_message()))
+                .collect(),
+        };
+
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    /// Looks up the document named in the request params and resolves it, returning the text,
+    /// the resolved program and the byte offset of the requested position.
+    fn analyze(&self, params: &Json) -> Option<(&str, Resolver, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.m_documents.get(uri)?;
+        let stmts = parse_program(text).ok()?;
+        let offset = position_to_offset(text, &params["position"]);
+
+        Some((text, Resolver::resolve(&stmts), offset))
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let uri = &params["textDocument"]["uri"];
+
+        self.analyze(params)
+            .and_then(|(text, resolver, offset)| {
+                let symbol = &resolver.get_symbols()[resolver.symbol_at(offset)?];
+                Some(json!({ "uri": uri, "range": range(text, symbol.get_span()) }))
+            })
+            .unwrap_or(Json::Null)
+    }
+
+    fn references(&self, params: &Json) -> Json {
+        let uri = &params["textDocument"]["uri"];
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+
+        self.analyze(params)
+            .and_then(|(text, resolver, offset)| {
+                let symbol = resolver.symbol_at(offset)?;
+                let mut spans = resolver.references_to(symbol);
+                if include_declaration {
+                    spans.insert(0, resolver.get_symbols()[symbol].get_span());
+                }
+
+                Some(Json::Array(
+                    spans
+                        .into_iter()
+                        .map(|span| json!({ "uri": uri, "range": range(text, span) }))
+                        .collect(),
+                ))
+            })
+            .unwrap_or(Json::Null)
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        self.analyze(params)
+            .and_then(|(_, resolver, offset)| {
+                let symbol = &resolver.get_symbols()[resolver.symbol_at(offset)?];
+                Some(json!({
+                    "contents": {
+                        "kind": "markdown",
+                        "value": format!("```lox\n{}\n```", symbol.signature()),
+                    },
+                }))
+            })
+            .unwrap_or(Json::Null)
+    }
+
+    fn document_symbols(&self, params: &Json) -> Json {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = self.m_documents.get(uri).map_or("", String::as_str);
+
+        match parse_program(text) {
+            Ok(stmts) => Json::Array(nested_symbols(text, &Resolver::resolve(&stmts), None)),
+            Err(_) => Json::Null,
+        }
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let mut items = Vec::new();
+
+        if let Some((_, resolver, offset)) = self.analyze(params) {
+            for symbol in resolver.visible_at(offset) {
+                items.push(json!({
+                    "label": symbol.get_name(),
+                    "kind": match symbol.get_kind() {
+                        SymbolKind::Function => 3,
+                        SymbolKind::Variable | SymbolKind::Parameter => 6,
+                        SymbolKind::Module => 9,
+                    },
+                    "detail": symbol.signature(),
+                }));
+            }
+        }
+
+        for keyword in keywords() {
+            items.push(json!({ "label": keyword, "kind": 14 }));
+        }
+
+        Json::Array(items)
+    }
+}
+
+fn nested_symbols(text: &str, resolver: &Resolver, container: Option<usize>) -> Vec<Json> {
+    resolver
+        .get_symbols()
+        .iter()
+        .enumerate()
+        .filter(|(_, symbol)| {
+            symbol.get_container() == container && symbol.get_kind() != SymbolKind::Parameter
+        })
+        .map(|(index, symbol)| {
+            json!({
+                "name": symbol.get_name(),
+                "detail": symbol.signature(),
+                "kind": match symbol.get_kind() {
+                    SymbolKind::Function => 12,
+                    SymbolKind::Module => 2,
+                    _ => 13,
+                },
+                "range": range(text, symbol.get_range()),
+                "selectionRange": range(text, symbol.get_span()),
+                "children": nested_symbols(text, resolver, Some(index)),
+            })
+        })
+        .collect()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn diagnostic(text: &str, span: Span, message: &str) -> Json {
+    json!({
+        "range": range(text, span),
+        "severity": 1,
+        "source": "rlox",
+        "message": message,
+    })
+}
+
+fn range(text: &str, span: Span) -> Json {
+    json!({
+        "start": offset_to_position(text, span.start()),
+        "end": offset_to_position(text, span.end()),
+    })
+}
+
+/// Converts a byte offset into an LSP position, whose character is counted in UTF-16 code units.
+fn offset_to_position(text: &str, offset: usize) -> Json {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = text[..line_start].matches('\n').count();
+    let character: usize = text[line_start..]
+        .char_indices()
+        .take_while(|(index, _)| line_start + index < offset)
+        .map(|(_, c)| c.len_utf16())
+        .sum();
+
+    json!({ "line": line, "character": character })
+}
+
+fn position_to_offset(text: &str, position: &Json) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+
+    let line_start = match line {
+        0 => 0,
+        line => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(index, _)| index + 1),
+    };
+
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+
+    text.len()
+}
+
+/// Reads a message framed with a `Content-Length` header, as used by both LSP and DAP.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Json>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()?;
+    Ok(())
+}
@@ -1,72 +1,314 @@
-// mod ast;
 mod ast_v2;
+mod compat;
+mod conformance;
+mod coverage;
+mod dap;
+mod debugger;
 mod environment;
+mod files;
+mod front_end;
+mod highlight;
+mod hook;
 mod interpreter;
+mod lexer;
+mod lsp;
+mod math;
+mod module;
+mod parser;
 mod parser_v2;
-// mod lexer;
-// mod parser;
-// mod token;
+mod profile;
+mod repl;
+mod resolver;
+mod strings;
+mod system;
+mod test_runner;
 mod token_v2;
+mod trace;
 mod value;
 mod visitor;
 
+use compat::*;
+use conformance::*;
+use coverage::*;
+use dap::*;
+use debugger::*;
+use front_end::*;
+use hook::*;
 use interpreter::*;
+use lsp::*;
+use profile::*;
+use repl::*;
+use test_runner::*;
+use trace::*;
 
 use anyhow::Result;
-use clap::Parser;
-use std::{fs, io::Write};
+use clap::{ArgGroup, Parser, Subcommand};
+use std::{cell::RefCell, fs, io::Read, path::Path, rc::Rc};
 
 // argument parser
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
+#[command(group(ArgGroup::new("input").args(["file", "script", "eval"]).multiple(true)))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(short, long, default_value = None)]
     file: Option<String>,
+
+    /// Script to run, as an alternative to --file, or `-` to read it from stdin
+    script: Option<String>,
+
+    /// Run the given code instead of a script
+    #[arg(short, long, conflicts_with = "file")]
+    eval: Option<String>,
+
+    /// Arguments passed to the script as the `args` list
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Print an indented log of statements, calls and variable updates to stderr
+    #[arg(long, requires = "input", conflicts_with_all = ["profile", "coverage"])]
+    trace: bool,
+
+    /// Time every function and print a table of calls and inclusive and exclusive time
+    #[arg(long, requires = "input", conflicts_with = "coverage")]
+    profile: bool,
+
+    /// Where --profile writes collapsed stacks for flamegraph.pl or inferno
+    #[arg(long, default_value = "profile.folded")]
+    profile_output: String,
+
+    /// Record executed lines and taken branches, printing a summary and writing an lcov file
+    #[arg(long, requires = "input")]
+    coverage: bool,
+
+    /// Where --coverage writes the lcov tracefile
+    #[arg(long, default_value = "lcov.info")]
+    coverage_output: String,
+
+    /// Follow the syntax and semantics of another Lox implementation
+    #[arg(long, value_enum, default_value_t = Dialect::Rlox)]
+    compat: Dialect,
+
+    /// Which parser reads the program
+    #[arg(long, value_enum, default_value_t = FrontEnd::Nom)]
+    parser: FrontEnd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start a language server speaking LSP over stdio
+    Lsp,
+    /// Step through a script with breakpoints
+    Debug { file: String },
+    /// Start a debug adapter speaking DAP over stdio
+    Dap,
+    /// Run the `.lox` and `.txt` scripts in a directory against their `// expect:` comments
+    Test {
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+    /// Run the vendored upstream Lox tests in clox mode and report where rlox differs
+    Conformance {
+        #[arg(default_value = "conformance")]
+        dir: String,
+    },
+    /// Parse scripts with both parsers and report where their syntax trees or errors differ
+    CompareParsers {
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+}
+
+/// A program to run: a script file, a script piped in on stdin, or the code given to `-e`.
+#[derive(Debug)]
+struct Program {
+    m_name: String,
+    m_source: String,
+    m_is_file: bool,
+}
+
+impl Program {
+    pub fn load(file: String) -> Result<Program> {
+        if file == "-" {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source)?;
+            return Ok(Program {
+                m_name: "<stdin>".into(),
+                m_source: source,
+                m_is_file: false,
+            });
+        }
+
+        Ok(Program {
+            m_source: fs::read_to_string(&file)?,
+            m_name: file,
+            m_is_file: true,
+        })
+    }
+
+    pub fn inline(code: String) -> Program {
+        Program {
+            m_name: "<eval>".into(),
+            m_source: code,
+            m_is_file: false,
+        }
+    }
+
+    /// An interpreter for the program, which resolves imports against the script's directory,
+    /// or the working directory when there is no script file.
+    pub fn interpreter(&self, args: Vec<String>) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        if self.m_is_file {
+            interpreter.set_path(Path::new(&self.m_name));
+        }
+        interpreter.set_args(args);
+        interpreter
+    }
 }
 
 #[derive(Debug)]
 struct App;
 
 impl App {
-    pub fn run_file_interpreter(file: String) -> Result<()> {
-        let file_string = fs::read_to_string(file)?;
+    pub fn run_file_interpreter(program: Program, args: Vec<String>, trace: bool) -> Result<()> {
+        let file_string = program.m_source.clone();
+
+        let mut interpreter = program.interpreter(args);
+        let mut hook: Hook = None;
+        if trace {
+            let tracer = Rc::new(RefCell::new(Tracer::new(file_string.clone())));
+            interpreter.set_hook(tracer.clone());
+            hook = Some(tracer);
+        }
+
+        // clox stops at the first error and reports it on stderr with a sysexits code
+        if dialect() == Dialect::Clox {
+            if let Err(err) = interpreter.run(&file_string) {
+                match &err {
+                    RunError::Parse(errors) => errors.iter().for_each(|err| eprintln!("{}", err)),
+                    RunError::Runtime(errors) => errors.iter().for_each(|err| eprintln!("{}", err)),
+                }
+                std::process::exit(err.exit_code());
+            }
+            return Ok(());
+        }
+
+        let result = interpreter.interpret(file_string);
+
+        let tests = UnitTests::run(take_tests(), &hook);
+        if !tests.is_empty() {
+            print!("{}", tests.report());
+        }
+
+        if let Err(err) = result {
+            std::process::exit(err.exit_code());
+        }
+        if !tests.passed() {
+            std::process::exit(1);
+        }
+
+        Ok(())
+    }
+
+    pub fn run_profiler(program: Program, args: Vec<String>, output: String) -> Result<()> {
+        let file_string = program.m_source.clone();
 
-        Interpreter::new().interpret(file_string);
+        let profiler = Rc::new(RefCell::new(Profiler::new(file_string.clone())));
+        let mut interpreter = program.interpreter(args);
+        interpreter.set_hook(profiler.clone());
+        let result = interpreter.interpret(file_string);
+
+        let mut profiler = profiler.borrow_mut();
+        eprint!("{}", format_table(&profiler.finish()));
+        fs::write(&output, profiler.collapsed_stacks())?;
+        eprintln!("collapsed stacks written to {}", output);
+
+        if let Err(err) = result {
+            std::process::exit(err.exit_code());
+        }
+
+        Ok(())
+    }
+
+    pub fn run_coverage(program: Program, args: Vec<String>, output: String) -> Result<()> {
+        let file_string = program.m_source.clone();
+
+        let coverage = Rc::new(RefCell::new(Coverage::new(file_string.clone())));
+        let mut interpreter = program.interpreter(args);
+        interpreter.set_hook(coverage.clone());
+        let result = interpreter.interpret(file_string);
+
+        let coverage = coverage.borrow();
+        eprint!("{}", coverage.summary());
+        fs::write(&output, coverage.lcov(&program.m_name))?;
+        eprintln!("lcov tracefile written to {}", output);
+
+        if let Err(err) = result {
+            std::process::exit(err.exit_code());
+        }
 
         Ok(())
     }
 
     pub fn run_repl_interpreter() -> Result<()> {
-        let mut input = String::new();
-        let mut read_buffer = String::new();
+        Repl::new()?.run()
+    }
+
+    pub fn run_debugger(file: String) -> Result<()> {
+        let file_string = fs::read_to_string(&file)?;
+
+        let debugger = Rc::new(RefCell::new(Debugger::new(file_string.clone())));
         let mut interpreter = Interpreter::new();
+        interpreter.set_path(Path::new(&file));
+        interpreter.set_hook(debugger.clone());
+        let _ = interpreter.interpret(file_string);
 
-        println!();
-        loop {
-            loop {
-                print!("|>  ");
-                std::io::stdout().flush()?;
-                std::io::stdin().read_line(&mut read_buffer)?;
+        if !debugger.borrow().has_quit() {
+            println!("program finished");
+        }
 
-                input.push_str(&read_buffer);
+        Ok(())
+    }
 
-                // allow multiline input by the user entering an empty line to end the input
-                match read_buffer.trim() {
-                    "" | "q" | "quit" => break,
-                    _ => (),
-                }
+    pub fn run_debug_adapter() -> Result<()> {
+        DebugAdapter::run(
+            Box::new(std::io::BufReader::new(std::io::stdin())),
+            Box::new(std::io::stdout()),
+        )
+    }
 
-                read_buffer.clear();
-            }
+    pub fn run_test_runner(dir: String) -> Result<()> {
+        if !run_tests(Path::new(&dir))? {
+            std::process::exit(1);
+        }
 
-            match input.trim() {
-                "q" | "quit" => break,
-                input => interpreter.interpret(input.into()),
-            }
+        Ok(())
+    }
+
+    pub fn run_conformance_suite(dir: String) -> Result<()> {
+        if !run_conformance(Path::new(&dir))? {
+            std::process::exit(1);
+        }
+
+        Ok(())
+    }
+
+    pub fn run_parser_comparison(dir: String) -> Result<()> {
+        if !compare_front_ends(Path::new(&dir))? {
+            std::process::exit(1);
+        }
+
+        Ok(())
+    }
 
-            println!();
+    pub fn run_language_server() -> Result<()> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
 
-            input.clear();
+        if !LanguageServer::new().run(stdin.lock(), stdout.lock())? {
+            std::process::exit(1);
         }
 
         Ok(())
@@ -75,10 +317,37 @@ impl App {
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    set_dialect(args.compat);
+    set_front_end(args.parser);
+
+    // with --file or --eval, the first positional argument already belongs to the script
+    let (program, script_args) = match (args.file, args.eval) {
+        (Some(file), _) => (
+            Some(Program::load(file)?),
+            args.script.into_iter().chain(args.args).collect(),
+        ),
+        (None, Some(code)) => (
+            Some(Program::inline(code)),
+            args.script.into_iter().chain(args.args).collect(),
+        ),
+        (None, None) => (args.script.map(Program::load).transpose()?, args.args),
+    };
 
-    match args.file {
-        Some(file) => App::run_file_interpreter(file)?,
-        None => App::run_repl_interpreter()?,
+    match (args.command, program) {
+        (Some(Command::Lsp), _) => App::run_language_server()?,
+        (Some(Command::Debug { file }), _) => App::run_debugger(file)?,
+        (Some(Command::Dap), _) => App::run_debug_adapter()?,
+        (Some(Command::Test { dir }), _) => App::run_test_runner(dir)?,
+        (Some(Command::Conformance { dir }), _) => App::run_conformance_suite(dir)?,
+        (Some(Command::CompareParsers { dir }), _) => App::run_parser_comparison(dir)?,
+        (None, Some(file)) if args.profile => {
+            App::run_profiler(file, script_args, args.profile_output)?
+        }
+        (None, Some(file)) if args.coverage => {
+            App::run_coverage(file, script_args, args.coverage_output)?
+        }
+        (None, Some(file)) => App::run_file_interpreter(file, script_args, args.trace)?,
+        (None, None) => App::run_repl_interpreter()?,
     };
 
     Ok(())
@@ -0,0 +1,89 @@
+use crate::environment::Environment;
+use crate::interpreter::{define_native, invalid_argument};
+use crate::value::*;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub fn number_arg(function: &str, value: &Value) -> Result<f64, String> {
+    value
+        .as_number()
+        .ok_or_else(|| invalid_argument(function, "a number", value))
+}
+
+// one and two argument functions of numbers are declared as `name => f64::method`
+macro_rules! unary {
+    ($env:expr, $($name:literal => $function:expr),* $(,)?) => {
+        $(define_native($env, $name, 1, |args| {
+            Ok(Value::Number($function(number_arg($name, &args[0])?)))
+        });)*
+    };
+}
+
+macro_rules! binary {
+    ($env:expr, $($name:literal => $function:expr),* $(,)?) => {
+        $(define_native($env, $name, 2, |args| {
+            Ok(Value::Number($function(
+                number_arg($name, &args[0])?,
+                number_arg($name, &args[1])?,
+            )))
+        });)*
+    };
+}
+
+pub fn define_math_natives(env: &Rc<RefCell<Environment>>) {
+    for (name, value) in [
+        ("PI", std::f64::consts::PI),
+        ("E", std::f64::consts::E),
+        ("INF", f64::INFINITY),
+        ("NAN", f64::NAN),
+    ] {
+        env.borrow_mut().define(name.into(), Value::Number(value));
+    }
+
+    unary!(env,
+        "floor" => f64::floor,
+        "ceil" => f64::ceil,
+        "round" => f64::round,
+        "trunc" => f64::trunc,
+        "abs" => f64::abs,
+        "sqrt" => f64::sqrt,
+        "exp" => f64::exp,
+        "ln" => f64::ln,
+        "log10" => f64::log10,
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "asin" => f64::asin,
+        "acos" => f64::acos,
+        "atan" => f64::atan,
+    );
+
+    binary!(env,
+        "pow" => f64::powf,
+        "atan2" => f64::atan2,
+        "hypot" => f64::hypot,
+        "min" => f64::min,
+        "max" => f64::max,
+    );
+
+    define_native(env, "clamp", 3, |args| {
+        let value = number_arg("clamp", &args[0])?;
+        let min = number_arg("clamp", &args[1])?;
+        let max = number_arg("clamp", &args[2])?;
+        match min <= max {
+            true => Ok(Value::Number(value.clamp(min, max))),
+            false => Err(format!("Invalid range => {}..{}", min, max)),
+        }
+    });
+
+    define_native(env, "is_nan", 1, |args| {
+        Ok(Value::Boolean(number_arg("is_nan", &args[0])?.is_nan()))
+    });
+
+    define_native(env, "is_finite", 1, |args| {
+        Ok(Value::Boolean(
+            number_arg("is_finite", &args[0])?.is_finite(),
+        ))
+    });
+}
@@ -0,0 +1,139 @@
+use crate::environment::Environment;
+use crate::front_end::*;
+use crate::hook::Hook;
+use crate::interpreter::define_natives;
+use crate::value::Value;
+use crate::visitor::StmtEvaluator;
+
+use itertools::Itertools;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+thread_local! {
+    static MODULES: RefCell<HashMap<PathBuf, Rc<Module>>> = RefCell::new(HashMap::new());
+    static SOURCES: RefCell<HashMap<PathBuf, Rc<str>>> = RefCell::new(HashMap::new());
+    // modules whose top level is still running, outermost first
+    static LOADING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A loaded module. Its exports are the names defined at its top level.
+#[derive(Debug)]
+pub struct Module {
+    m_path: PathBuf,
+    m_env: Rc<RefCell<Environment>>,
+}
+
+impl Module {
+    pub fn get_name(&self) -> String {
+        self.m_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn get_names(&self) -> Vec<String> {
+        self.m_env.borrow().get_scope().keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.m_env.borrow().get_scope().get(name).cloned()
+    }
+}
+
+/// The source of the module at `path`, once an import has read it. Spans in the module's code
+/// point into this source rather than the main script's.
+pub fn module_source(path: &Path) -> Option<Rc<str>> {
+    SOURCES.with(|sources| sources.borrow().get(path).cloned())
+}
+
+/// Forgets every loaded module, so the next import runs it again.
+pub fn clear_modules() {
+    MODULES.with(|modules| modules.borrow_mut().clear());
+    SOURCES.with(|sources| sources.borrow_mut().clear());
+    LOADING.with(|loading| loading.borrow_mut().clear());
+}
+
+/// The file name of a module, which is how errors and tools refer to it.
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Loads the module at `path`, resolved against the file that `importer` belongs to, or the
+/// working directory outside of a file. A module runs once, under the importer's `hook`; later
+/// imports share its exports.
+pub fn import(
+    path: &str,
+    importer: &Rc<RefCell<Environment>>,
+    hook: &Hook,
+) -> Result<Rc<Module>, String> {
+    let base = importer
+        .borrow()
+        .get_path()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let path = base
+        .join(path)
+        .canonicalize()
+        .map_err(|err| format!("Could not find module => {}: {}", path, err))?;
+
+    if let Some(module) = MODULES.with(|modules| modules.borrow().get(&path).cloned()) {
+        return Ok(module);
+    }
+
+    if let Some(start) = LOADING.with(|loading| loading.borrow().iter().position(|p| *p == path)) {
+        let cycle = LOADING.with(|loading| {
+            loading.borrow()[start..]
+                .iter()
+                .chain([&path])
+                .map(|path| file_name(path))
+                .join(" -> ")
+        });
+        return Err(format!("Import cycle => {}", cycle));
+    }
+
+    let source = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Could not read module => {}: {}", path.display(), err))?;
+    let stmts = parse_program(&source).map_err(|errors| {
+        format!(
+            "Invalid module => {}\n{}",
+            path.display(),
+            errors.iter().join("\n")
+        )
+    })?;
+    SOURCES.with(|sources| sources.borrow_mut().insert(path.clone(), source.into()));
+
+    let prelude = Environment::new();
+    define_natives(&prelude);
+    let env = Environment::new_scope(&prelude);
+    env.borrow_mut().set_path(&path);
+    env.borrow_mut().set_module();
+
+    LOADING.with(|loading| loading.borrow_mut().push(path.clone()));
+    let result = stmts.iter().try_for_each(|stmt| {
+        let mut visitor = StmtEvaluator::new(&env, hook);
+        visitor.execute(stmt);
+        visitor.get_result()
+    });
+    LOADING.with(|loading| loading.borrow_mut().pop());
+
+    result.map_err(|errors| {
+        format!(
+            "Error in module {} => {}",
+            file_name(&path),
+            errors.iter().join("; ")
+        )
+    })?;
+
+    let module = Rc::new(Module {
+        m_path: path.clone(),
+        m_env: env,
+    });
+    MODULES.with(|modules| modules.borrow_mut().insert(path, module.clone()));
+    Ok(module)
+}
@@ -1,258 +1,322 @@
-use crate::ast::*;
-use crate::token::*;
-
-use anyhow::Result;
-use itertools::Itertools;
-use std::iter::Peekable;
+use crate::ast_v2::*;
+use crate::compat::*;
+use crate::lexer::*;
+use crate::parser_v2::{starts_token, ParseError, EXPECTED_EXPRESSION, RESERVED_WORD};
+use crate::token_v2::*;
 
+/// Whether the next token is one of the given kinds, ignoring the data they carry.
 macro_rules! match_token {
     ($self:ident, [$($token_type:ident $(($($inner:tt)*))? ),*]) => {
-        $self.peek_next().is_some_and(|token| match token.get_token_type() {
+        match $self.peek().get_token_type() {
             $(
                 TokenType::$token_type $(($($inner)*))? => true,
             )*
             _ => false,
-        })
+        }
     };
 }
 
-macro_rules! multi_match_token {
-    ($self:ident, [$([$($token_type:ident $(($($inner:tt)*))? ),*]),*]) => {{
-        let mut iter = $self.m_token_iter.clone().multipeek();
-        let mut result = true;
-
-        $(
-            result &= iter.peek().map_or(false, |token| {
-                match token.get_token_type() {
-                    $(
-                        // Match for variants with and without data
-                        TokenType::$token_type $(($($inner)*))? => true,
-                        _ => false,
-                    )*
-                }
-            });
-        )*
-
-        result
-    }};
+/// Takes the next token if it is of the given kind, or fails with `message`.
+macro_rules! consume {
+    ($self:ident, $token_type:ident $(($($inner:tt)*))?, $message:expr) => {
+        match match_token!($self, [$token_type $(($($inner)*))?]) {
+            true => Ok($self.take_next()),
+            false => Err($self.error($message)),
+        }
+    };
 }
 
+/// A recursive descent parser over the tokens of `Lexer`. It accepts the same language as the
+/// nom parser in `parser_v2` and builds the same syntax trees, spans included, so either can
+/// stand in for the other.
 #[derive(Debug)]
-pub struct Parser {
-    m_token_iter: Peekable<std::vec::IntoIter<Token>>,
-    m_current: Option<Token>,
-    m_previous: Option<Token>,
-    m_errors: Vec<String>,
+pub struct Parser<'a> {
+    m_source: &'a str,
+    m_tokens: Vec<Token>,
+    m_current: usize,
+    m_errors: Vec<ParseError>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str, tokens: Vec<Token>) -> Self {
         Self {
-            m_token_iter: tokens.into_iter().peekable(),
-            m_current: None,
-            m_previous: None,
+            m_source: source,
+            m_tokens: tokens,
+            m_current: 0,
             m_errors: Vec::new(),
         }
     }
 
-    fn peek_next(&mut self) -> Option<&Token> {
-        self.m_token_iter.peek()
+    fn peek(&self) -> &Token {
+        &self.m_tokens[self.m_current]
     }
 
-    fn take_next(&mut self) -> Option<Token> {
-        self.m_previous = self.m_current.take();
-        self.m_current = self.m_token_iter.next();
-        self.m_current.clone()
+    fn peek_nth(&self, n: usize) -> &Token {
+        let last = self.m_tokens.len() - 1;
+        &self.m_tokens[(self.m_current + n).min(last)]
     }
 
-    fn sync(&mut self) {
-        while let Some(token) = self.take_next() {
-            if token.get_token_type() == &TokenType::Semicolon {
-                return;
-            }
+    fn previous(&self) -> Option<&Token> {
+        self.m_current
+            .checked_sub(1)
+            .map(|index| &self.m_tokens[index])
+    }
 
-            if self
-                .peek_next()
-                .is_some_and(|token| &TokenType::Return == token.get_token_type())
-            {
-                return;
-            }
+    fn take_next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.get_token_type() != &TokenType::Eof {
+            self.m_current += 1;
         }
+        token
     }
 
-    fn primary(&mut self) -> Result<Expr> {
-        if multi_match_token!(self, [[Fun], [LeftParen]]) {
-            self.take_next();
-            self.take_next();
-
-            let mut parameters = Vec::new();
-            if !match_token!(self, [RightParen]) {
-                loop {
-                    if parameters.len() >= 255 {
-                        self.m_errors.push(format!(
-                            "Cannot have more than 255 parameters\n    => line {} | column {}",
-                            self.m_current.as_ref().unwrap().get_line_number(),
-                            self.m_current.as_ref().unwrap().get_col_range().start + 1
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
+    /// Whether a line break separates the next token from the one before it. A comment takes in
+    /// the line break ending it.
+    fn starts_line(&self) -> bool {
+        self.previous().is_some_and(|previous| {
+            previous.get_token_type() == &TokenType::Comment
+                || self
+                    .m_source
+                    .get(previous.get_span().end()..self.peek().get_span().start())
+                    .is_some_and(|gap| gap.contains('\n'))
+        })
+    }
 
-                    parameters.push(self.take_next().unwrap());
+    /// An error where something expected after the previous token is missing: at the next token,
+    /// or at the end of the line when the next token is on a later one, where a missing `;`
+    /// belongs.
+    fn error(&self, message: &str) -> ParseError {
+        let start = match self.previous() {
+            Some(previous) => {
+                let rest = &self.m_source[previous.get_span().end()..];
+                self.m_source.len() - rest.trim_start_matches([' ', '\t']).len()
+            }
+            None => self.peek().get_span().start(),
+        };
+        self.error_at(message, start)
+    }
 
-                    if !match_token!(self, [Comma]) {
-                        break;
-                    }
+    /// An error at the first character from `start`.
+    fn error_at(&self, message: &str, start: usize) -> ParseError {
+        let width = self.m_source[start..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+        ParseError::new(
+            message.into(),
+            Span::new(start, start + width),
+            self.m_source,
+        )
+    }
 
-                    self.take_next();
-                }
+    /// Records an error. A character no token starts with is the mistake, whatever was expected
+    /// there.
+    fn report(&mut self, err: ParseError) {
+        let start = err.get_span().start();
+        let err = match self.m_source[start..].chars().next() {
+            Some(c) if !starts_token(c) => {
+                self.error_at(&format!("Unexpected character '{}'", c), start)
             }
+            _ => err,
+        };
+        // the `}` missing at the end reports once, not once for every block left open
+        if !self.m_errors.contains(&err) {
+            self.m_errors.push(err);
+        }
+    }
 
-            if match_token!(self, [RightParen]) {
-                self.take_next();
-            } else {
-                self.m_errors.push(format!(
-                    "Expected ')' after function parameters\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
+    /// Runs `parse`, going back to where it started when it fails without saying what it
+    /// expected, so that the caller can try something else or report what it expected instead.
+    fn attempt<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Option<T>, ParseError> {
+        let start = self.m_current;
+        match parse(self) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.get_message() == EXPECTED_EXPRESSION => {
+                self.m_current = start;
+                Ok(None)
             }
+            Err(err) => Err(err),
+        }
+    }
 
-            if match_token!(self, [LeftBrace]) {
-                let body = self.statement()?;
-                let body = match body {
-                    Stmt::Block { m_statements } => m_statements,
-                    _ => {
-                        self.m_errors.push(format!(
-                            "Expected block after function declaration\n    => line {} | column {}",
-                            self.m_previous.as_ref().unwrap().get_line_number(),
-                            self.m_previous.as_ref().unwrap().get_col_range().start + 1,
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
-                };
-                return Ok(Expr::new_function(parameters, body));
-            } else {
-                self.m_errors.push(format!(
-                    "Expected '{{' after function declaration\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
+    /// The error for a malformed literal next that `is_reported` accepts, as the nom parser gives
+    /// where it tries to read one.
+    fn invalid_literal(&self, is_reported: impl Fn(&str) -> bool) -> Option<ParseError> {
+        match self.peek().get_token_type() {
+            TokenType::Error(message) if is_reported(message) => {
+                Some(self.error_at(message, self.peek().get_span().end()))
             }
+            _ => None,
         }
+    }
 
-        if match_token!(self, [False, True, String(_), Number(_), Nil]) {
-            return Ok(Expr::new_literal(self.take_next().unwrap()));
+    /// The path of an import, which must be a string.
+    fn module_path(&mut self, message: &str) -> Result<Token, ParseError> {
+        match self.invalid_literal(|message| message == "Unterminated string") {
+            Some(err) => Err(err),
+            None if match_token!(self, [String(_)]) => Ok(self.string().0),
+            None => Err(self.error(message)),
         }
+    }
 
-        if match_token!(self, [Identifier(_)]) {
-            return Ok(Expr::new_variable(self.take_next().unwrap()));
+    /// Takes the string next with what it is made of, recording any invalid escape sequence in
+    /// it without failing, as that does not stop the rest of the program from being parsed.
+    fn string(&mut self) -> (Token, Option<StringLiteral>) {
+        let token = self.take_next();
+        let start = token.get_span().start();
+        let literal = StringLiteral::scan(&self.m_source[start..]);
+        for (message, span) in literal.iter().flat_map(StringLiteral::get_errors) {
+            let span = Span::new(start + span.start(), start + span.end());
+            let err = ParseError::new(message.clone(), span, self.m_source);
+            if !self.m_errors.contains(&err) {
+                self.m_errors.push(err);
+            }
         }
 
-        if match_token!(self, [LeftParen]) {
-            self.take_next();
-            let expr = self.expression()?;
-
-            while !match_token!(self, [RightParen]) {
-                if self.take_next().is_none() {
-                    self.m_errors.push(format!(
-                        "Unterminated grouping, expected ')'\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
+        (token, literal)
+    }
+
+    /// The string next, which is an interpolation when it embeds expressions with `${...}`.
+    fn text(&mut self) -> Expr {
+        let (token, literal) = self.string();
+        let Some(literal) = literal.filter(StringLiteral::is_interpolated) else {
+            return Expr::new_literal(token);
+        };
+
+        let start = token.get_span().start();
+        let parts = literal
+            .get_parts()
+            .iter()
+            .map(|part| match part {
+                StringPart::Text(text, span) => Expr::new_literal(Token::new_token(
+                    TokenType::String(text.clone()),
+                    Span::new(start + span.start(), start + span.end()),
+                )),
+                StringPart::Code(span) => {
+                    self.embedded(Span::new(start + span.start(), start + span.end()))
                 }
-            }
+            })
+            .collect();
+        Expr::new_interpolation(parts, token.get_span())
+    }
 
-            self.take_next();
+    /// The expression embedded in a string by `${...}` at `span`, parsed from tokens of its own.
+    /// A mistake in it is recorded without failing, like an invalid escape sequence, and leaves
+    /// `nil` in its place.
+    fn embedded(&mut self, span: Span) -> Expr {
+        let tokens = Lexer::new_within(self.m_source, span).tokenize();
+        // the parser ends where the expression does, as the source past it is the string's
+        let mut parser = Parser::new(&self.m_source[..span.end()], tokens);
+        let expr = parser
+            .assignment()
+            .and_then(|expr| match match_token!(parser, [Eof]) {
+                true => Ok(expr),
+                false => Err(parser.error("Expected '}' after expression")),
+            });
 
-            return Ok(Expr::new_grouping(Box::new(expr)));
+        if let Err(err) = &expr {
+            parser.report(err.clone());
+        }
+        for err in parser.m_errors {
+            if !self.m_errors.contains(&err) {
+                self.m_errors.push(err);
+            }
         }
+        expr.unwrap_or_else(|_| Expr::new_literal(Token::new_token(TokenType::Nil, span)))
+    }
 
-        if let Some(token) = self.m_previous.as_ref() {
-            self.m_errors.push(format!(
-                "Invalid operands, expected expression\n    => line {} | column {}",
-                token.get_line_number(),
-                token.get_col_range().start + 1
-            ));
-        } else if let Some(token) = self.m_current.as_ref() {
-            self.m_errors.push(format!(
-                "Invalid operands, expected expression\n    => line {} | column {}",
-                token.get_line_number(),
-                token.get_col_range().start + 1
-            ));
-        } else {
-            self.m_errors
-                .push("Invalid operands, expected expression".to_string());
+    /// Takes an identifier, reporting a keyword in its place as a reserved word.
+    fn name(&mut self, message: &str) -> Result<Token, ParseError> {
+        let span = self.peek().get_span();
+        let text = &self.m_source[span.start()..span.end()];
+        match self.peek().get_token_type() {
+            TokenType::Identifier(_) => Ok(self.take_next()),
+            _ if is_keyword(text) => Err(ParseError::new(
+                format!("'{}' {}", text, RESERVED_WORD),
+                span,
+                self.m_source,
+            )),
+            _ => Err(self.error(message)),
         }
+    }
 
-        Err(anyhow::anyhow!(""))
+    fn parameters(&mut self) -> Result<Vec<Token>, ParseError> {
+        let mut parameters = Vec::new();
+        if !match_token!(self, [RightParen]) {
+            parameters.push(self.name("Expected parameter name")?);
+            while match_token!(self, [Comma]) {
+                self.take_next();
+                parameters.push(self.name("Expected parameter name after ','")?);
+            }
+        }
+        consume!(self, RightParen, "Expected ')' after parameters")?;
+
+        Ok(parameters)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
-        let mut arguments = Vec::new();
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        if match_token!(self, [Fun]) {
+            self.take_next();
+            consume!(self, LeftParen, "Expected '(' after 'fun'")?;
+            let parameters = self.parameters()?;
+            let body = self.block("Expected '{' before function body")?;
+            return Ok(Expr::new_function(parameters, Box::new(body)));
+        }
 
-        if !match_token!(self, [RightParen]) {
-            loop {
-                if arguments.len() >= 255 {
-                    self.m_errors.push(format!(
-                        "Cannot have more than 255 arguments\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
+        if match_token!(self, [String(_)]) {
+            return Ok(self.text());
+        }
 
-                arguments.push(self.expression()?);
+        if match_token!(self, [False, True, Number(_), Nil]) {
+            return Ok(Expr::new_literal(self.take_next()));
+        }
 
-                if !match_token!(self, [Comma]) {
-                    break;
-                }
+        if match_token!(self, [Identifier(_)]) {
+            return Ok(Expr::new_variable(self.take_next()));
+        }
 
-                self.take_next();
-            }
+        if let Some(err) = self.invalid_literal(|_| true) {
+            return Err(err);
         }
 
-        match self.take_next() {
-            Some(token) => {
-                if token.get_token_type() == &TokenType::RightParen {
-                    Ok(Expr::new_call(
-                        Box::new(callee),
-                        token,
-                        arguments.into_iter().collect(),
-                    ))
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ')' after arguments\n    => line {} | column {}",
-                        token.get_line_number().saturating_sub(1),
-                        token.get_col_range().start + 1
-                    ));
-                    Err(anyhow::anyhow!(""))
-                }
-            }
-            None => {
-                self.m_errors.push(format!(
-                    "Expected ')' after arguments\n    => line {} | column {}",
-                    self.m_previous
-                        .as_ref()
-                        .unwrap()
-                        .get_line_number()
-                        .saturating_sub(1),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                Err(anyhow::anyhow!(""))
+        if match_token!(self, [LeftParen]) {
+            self.take_next();
+            let expr = self.expression("Expected expression after '('")?;
+            consume!(self, RightParen, "Expected ')' after expression")?;
+            return Ok(Expr::new_grouping(Box::new(expr)));
+        }
+
+        Err(self.error_at(EXPECTED_EXPRESSION, self.peek().get_span().start()))
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+        if let Some(first) = self.attempt(Self::assignment)? {
+            arguments.push(first);
+            while match_token!(self, [Comma]) {
+                self.take_next();
+                arguments.push(self.expression("Expected expression after ','")?);
             }
         }
+        consume!(self, RightParen, "Expected ')' after arguments")?;
+
+        Ok(Expr::new_call(Box::new(callee), arguments))
     }
 
-    fn call(&mut self) -> Result<Expr> {
+    fn call(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.primary()?;
 
         loop {
             if match_token!(self, [LeftParen]) {
                 self.take_next();
                 expr = self.finish_call(expr)?;
+            } else if match_token!(self, [Dot]) {
+                self.take_next();
+                let name = self.name("Expected property name after '.'")?;
+                expr = Expr::new_get(Box::new(expr), name);
             } else {
                 break;
             }
@@ -261,619 +325,441 @@ impl Parser {
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr> {
+    fn unary(&mut self) -> Result<Expr, ParseError> {
         if match_token!(self, [Bang, Minus]) {
-            let operator = self.take_next().unwrap();
-            let right = self.unary()?;
-            return Ok(Expr::new_unary(operator, Box::new(right)));
+            let operator = self.take_next();
+            return match self.attempt(Self::unary)? {
+                Some(right) => Ok(Expr::new_unary(operator, Box::new(right))),
+                None => Err(self.error_at(EXPECTED_EXPRESSION, operator.get_span().start())),
+            };
         }
 
         self.call()
     }
 
-    fn factor(&mut self) -> Result<Expr> {
+    /// An operand on the right of a binary operator.
+    fn operand(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        self.expected(operand, "Expected expression after operator")
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
 
         while match_token!(self, [Slash, Star]) {
-            let operator = self.take_next().unwrap();
-            let right = self.unary()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::unary)?;
             expr = Expr::new_binary(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr> {
+    fn term(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.factor()?;
 
         while match_token!(self, [Minus, Plus]) {
-            let operator = self.take_next().unwrap();
-            let right = self.factor()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::factor)?;
             expr = Expr::new_binary(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr> {
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.term()?;
 
         while match_token!(self, [Greater, GreaterEqual, Less, LessEqual]) {
-            let operator = self.take_next().unwrap();
-            let right = self.term()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::term)?;
             expr = Expr::new_binary(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr> {
+    fn equality(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.comparison()?;
 
         while match_token!(self, [BangEqual, EqualEqual]) {
-            let operator = self.take_next().unwrap();
-            let right = self.comparison()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::comparison)?;
             expr = Expr::new_binary(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn and(&mut self) -> Result<Expr> {
+    fn and(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.equality()?;
 
         while match_token!(self, [And]) {
-            let operator = self.take_next().unwrap();
-            let right = self.equality()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::equality)?;
             expr = Expr::new_logical(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn or(&mut self) -> Result<Expr> {
+    fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
         while match_token!(self, [Or]) {
-            let operator = self.take_next().unwrap();
-            let right = self.and()?;
+            let operator = self.take_next();
+            let right = self.operand(Self::and)?;
             expr = Expr::new_logical(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn assignment(&mut self) -> Result<Expr> {
-        let expr = self.or()?;
-
-        if match_token!(self, [Equal]) {
-            let equals = self.take_next().unwrap();
-            let value = self.assignment()?;
-
-            if let Expr::Variable { m_token } = expr {
-                return Ok(Expr::new_assign(m_token, Box::new(value)));
+    /// Only a plain variable can be assigned to, as in the nom parser.
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        if match_token!(self, [Identifier(_)])
+            && self.peek_nth(1).get_token_type() == &TokenType::Equal
+        {
+            let start = self.m_current;
+            let name = self.take_next();
+            self.take_next();
+            match self.attempt(Self::assignment)? {
+                Some(value) => return Ok(Expr::new_assign(name, Box::new(value))),
+                None => self.m_current = start,
             }
-
-            self.m_errors.push(format!(
-                "Invalid assignment target\n    => line {} | column {}",
-                equals.get_line_number(),
-                equals.get_col_range().start + 1
-            ));
-            return Err(anyhow::anyhow!(""));
         }
 
-        Ok(expr)
+        self.or()
     }
 
-    fn expression(&mut self) -> Result<Expr> {
-        self.assignment()
-    }
-
-    fn statement(&mut self) -> Result<Stmt> {
-        // if self.matches(&[TokenType::Print]) {
-        //     self.take_next();
-        //     let expr = self.expression()?;
-        //     match self.take_next() {
-        //         Some(token) => {
-        //             if token.get_token_type() == &TokenType::Semicolon {
-        //             } else {
-        //                 self.m_errors.push(format!(
-        //                     "Expected ';' after expression\n    => line {} | column {}",
-        //                     token.get_line_number().saturating_sub(1),
-        //                     token.get_col_range().start + 1
-        //                 ));
-        //                 self.sync();
-        //             }
-        //         }
-        //         None => {
-        //             self.m_errors.push(format!(
-        //                 "Expected ';' after expression\n    => line {} | column {}",
-        //                 self.m_previous
-        //                     .as_ref()
-        //                     .unwrap()
-        //                     .get_line_number()
-        //                     .saturating_sub(1),
-        //                 self.m_previous.as_ref().unwrap().get_col_range().start + 1
-        //             ));
-        //             self.sync();
-        //         }
-        //     }
-        //     return Ok(Stmt::new_print(expr));
-        // }
+    /// Something `parse` must find next, failing with `message` where it is missing.
+    fn expected<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+        message: &str,
+    ) -> Result<T, ParseError> {
+        match self.attempt(parse)? {
+            Some(value) => Ok(value),
+            None => Err(self.error(message)),
+        }
+    }
 
-        if match_token!(self, [LeftBrace]) {
-            self.take_next();
-            let mut statements = Vec::new();
-            while !match_token!(self, [RightBrace]) {
-                if self.peek_next().is_none() {
-                    self.m_errors.push(format!(
-                        "Unterminated block, expected '}}'\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
+    /// An expression that must follow, failing with `message` where it is missing.
+    fn expression(&mut self, message: &str) -> Result<Expr, ParseError> {
+        self.expected(Self::assignment, message)
+    }
+
+    /// The declarations up to the `}` ending a block, or up to the end of the source. A
+    /// declaration that fails is recorded and skipped.
+    fn declarations(&mut self, in_block: bool) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            match self.peek().get_token_type() {
+                TokenType::Eof if in_block => {
+                    return Err(ParseError::new(
+                        "Expected '}' after block".into(),
+                        self.peek().get_span(),
+                        self.m_source,
+                    ))
+                }
+                TokenType::Eof => return Ok(statements),
+                TokenType::RightBrace if in_block => return Ok(statements),
+                TokenType::RightBrace => {
+                    let brace = self.take_next();
+                    self.m_errors.push(ParseError::new(
+                        "Unexpected '}'".into(),
+                        brace.get_span(),
+                        self.m_source,
                     ));
-                    return Err(anyhow::anyhow!(""));
+                    continue;
                 }
-                statements.push(self.declaration()?);
+                _ => (),
             }
-            self.take_next();
-            return Ok(Stmt::new_block(statements));
-        }
 
-        if match_token!(self, [If]) {
-            self.take_next();
-            if match_token!(self, [LeftParen]) {
-                self.take_next();
-                let condition = self.expression()?;
-                if match_token!(self, [RightParen]) {
-                    self.take_next();
-                    let then_branch = Box::new(self.statement()?);
-                    let else_branch = if match_token!(self, [Else]) {
+            let start = self.m_current;
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(err) => {
+                    let at = err.get_span().start();
+                    self.report(err);
+                    self.sync(at);
+                    // always move on, even when the error is where the declaration started
+                    if self.m_current == start {
                         self.take_next();
-                        Some(Box::new(self.statement()?))
-                    } else {
-                        None
-                    };
-                    return Ok(Stmt::new_if(condition, then_branch, else_branch));
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ')' after if condition\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
+                    }
                 }
-            } else {
-                self.m_errors.push(format!(
-                    "Expected '(' after 'if'\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
             }
         }
+    }
 
-        if match_token!(self, [While]) {
-            self.take_next();
-            if match_token!(self, [LeftParen]) {
-                self.take_next();
-                let condition = self.expression()?;
-                if match_token!(self, [RightParen]) {
-                    self.take_next();
-                    let body = Box::new(self.statement()?);
-                    return Ok(Stmt::new_while(condition, body));
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ')' after while condition\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
-            } else {
-                self.m_errors.push(format!(
-                    "Expected '(' after 'while'\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
-            }
-        }
+    fn block(&mut self, message: &str) -> Result<Stmt, ParseError> {
+        let open = consume!(self, LeftBrace, message)?;
+        let statements = self.declarations(true)?;
+        let close = self.take_next();
 
-        if match_token!(self, [For]) {
-            self.take_next();
-            if match_token!(self, [LeftParen]) {
-                self.take_next();
-                let initializer = if match_token!(self, [Semicolon]) {
-                    None
-                } else if multi_match_token!(self, [[Var], [Identifier(_)], [Equal]]) {
-                    self.take_next();
-                    let name = self.take_next().unwrap();
-
-                    if !matches!(name.get_token_type(), TokenType::Identifier(_)) {
-                        self.m_errors.push(format!(
-                            "Expected identifier after 'var'\n    => line {} | column {}",
-                            name.get_line_number().saturating_sub(1),
-                            name.get_col_range().start + 1
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
+        Ok(Stmt::new_block(
+            statements,
+            open.get_span().to(close.get_span()),
+        ))
+    }
 
-                    let initializer = if match_token!(self, [Equal]) {
-                        self.take_next();
-                        Some(self.expression()?)
-                    } else {
-                        None
-                    };
-
-                    match self.take_next() {
-                        Some(token) => {
-                            if token.get_token_type() == &TokenType::Semicolon {
-                            } else {
-                                self.m_errors.push(format!(
-                                    "Expected ';' after variable declaration\n    => line {} | column {}",
-                                    token.get_line_number().saturating_sub(1),
-                                    token.get_col_range().start + 1
-                                ));
-                                return Err(anyhow::anyhow!(""));
-                            }
-                        }
-                        None => {
-                            self.m_errors.push(format!(
-                                "Expected ';' after variable declaration\n    => line {} | column {}",
-                                self.m_previous
-                                    .as_ref()
-                                    .unwrap()
-                                    .get_line_number()
-                                    .saturating_sub(1),
-                                self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                            ));
-                            return Err(anyhow::anyhow!(""));
-                        }
-                    }
+    /// The body of `if`, `while` and `for`, which must be a block unless following clox.
+    fn body(&mut self, message: &str) -> Result<Stmt, ParseError> {
+        match dialect() {
+            Dialect::Rlox => self.block(message),
+            Dialect::Clox => self.expected(Self::statement, message),
+        }
+    }
 
-                    Some(Stmt::new_expression(Expr::new_assign(
-                        name,
-                        Box::new(initializer.unwrap()),
-                    )))
-                } else {
-                    let expr = self.expression()?;
-                    if match_token!(self, [Semicolon]) {
-                        self.take_next();
-                    } else {
-                        self.m_errors.push(format!(
-                            "Expected ';' after for loop initializer\n    => line {} | column {}",
-                            self.m_previous.as_ref().unwrap().get_line_number(),
-                            self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr = self.assignment()?;
+        consume!(self, Semicolon, "Expected ';' after expression")?;
+        Ok(Stmt::new_expression(expr))
+    }
 
-                    Some(Stmt::new_expression(expr))
-                };
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if match_token!(self, [LeftBrace]) {
+            return self.block("Expected '{'");
+        }
 
-                let condition = if match_token!(self, [Semicolon]) {
-                    None
-                } else {
-                    Some(self.expression()?)
-                };
+        if match_token!(self, [Print]) {
+            let keyword = self.take_next();
+            let value = self.expression("Expected expression after 'print'")?;
+            consume!(self, Semicolon, "Expected ';' after value")?;
+
+            let println =
+                Token::new_token(TokenType::new_identifier("println"), keyword.get_span());
+            return Ok(Stmt::new_expression(Expr::new_call(
+                Box::new(Expr::new_variable(println)),
+                vec![value],
+            )));
+        }
 
-                if match_token!(self, [Semicolon]) {
-                    self.take_next();
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ';' after for loop condition\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
+        if match_token!(self, [While]) {
+            self.take_next();
+            consume!(self, LeftParen, "Expected '(' after 'while'")?;
+            let condition = self.expression("Expected condition after '('")?;
+            consume!(self, RightParen, "Expected ')' after while condition")?;
+            let body = self.body("Expected body after while condition")?;
+            return Ok(Stmt::new_while(condition, Box::new(body)));
+        }
 
-                let increment = if match_token!(self, [RightParen]) {
-                    None
-                } else {
-                    Some(self.expression()?)
-                };
+        if match_token!(self, [Return]) {
+            let keyword = self.take_next();
+            let value = self.attempt(Self::assignment)?;
+            consume!(self, Semicolon, "Expected ';' after return value")?;
+            return Ok(Stmt::new_return(keyword, value));
+        }
 
-                if match_token!(self, [RightParen]) {
+        if match_token!(self, [If]) {
+            self.take_next();
+            consume!(self, LeftParen, "Expected '(' after 'if'")?;
+            let condition = self.expression("Expected condition after '('")?;
+            consume!(self, RightParen, "Expected ')' after if condition")?;
+            let then_branch = self.body("Expected body after if condition")?;
+            let else_branch = match match_token!(self, [Else]) {
+                true => {
                     self.take_next();
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ')' after for loop increment\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
-
-                let mut body = Box::new(self.statement()?);
-
-                if let Some(increment) = increment {
-                    body = Box::new(Stmt::new_block(vec![
-                        *body,
-                        Stmt::new_expression(increment),
-                    ]));
+                    Some(Box::new(self.body("Expected body after 'else'")?))
                 }
+                false => None,
+            };
+            return Ok(Stmt::new_if(condition, Box::new(then_branch), else_branch));
+        }
 
-                if let Some(condition) = condition {
-                    body = Box::new(Stmt::new_while(condition, body));
-                }
+        if match_token!(self, [For]) {
+            return self.for_statement();
+        }
 
-                if let Some(initializer) = initializer {
-                    let initializer_definition = match initializer {
-                        Stmt::Expression { m_expression } => match m_expression {
-                            Expr::Assign {
-                                m_token: m_name,
-                                m_value,
-                            } => Stmt::new_var(m_name, Some(*m_value), vec![*body]),
-                            _ => {
-                                self.m_errors.push(format!(
-                                    "Expected expression after 'var'\n    => line {} | column {}",
-                                    self.m_previous.as_ref().unwrap().get_line_number(),
-                                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                                ));
-                                return Err(anyhow::anyhow!(""));
-                            }
-                        },
-                        _ => {
-                            self.m_errors.push(format!(
-                                "Expected expression after 'var'\n    => line {} | column {}",
-                                self.m_previous.as_ref().unwrap().get_line_number(),
-                                self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                            ));
-                            return Err(anyhow::anyhow!(""));
-                        }
-                    };
-                    body = Box::new(initializer_definition);
-                }
+        self.expression_statement()
+    }
 
-                return Ok(*body);
-            } else {
-                self.m_errors.push(format!(
-                    "Expected '(' after 'for'\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
-            }
-        }
+    /// `for` becomes a block holding the initializer and a `while` loop, spanning the whole
+    /// statement, with `true` and `nil` standing in for a missing condition and increment.
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.take_next();
+        consume!(self, LeftParen, "Expected '(' after 'for'")?;
 
-        let expr = self.expression()?;
-        match self.take_next() {
-            Some(token) => {
-                if token.get_token_type() == &TokenType::Semicolon {
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ';' after expression\n    => line {} | column {}",
-                        token.get_line_number().saturating_sub(1),
-                        token.get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
-            }
+        let (initializer, declared) = match self.attempt(Self::declaration)? {
+            Some(initializer) => (initializer, true),
             None => {
-                self.m_errors.push(format!(
-                    "Expected ';' after expression\n    => line {} | column {}",
-                    self.m_previous
-                        .as_ref()
-                        .unwrap()
-                        .get_line_number()
-                        .saturating_sub(1),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
+                let semicolon = consume!(self, Semicolon, "Expected for loop initializer or ';'")?;
+                let nil = Token::new_token(TokenType::Nil, semicolon.get_span());
+                (Stmt::new_expression(Expr::new_literal(nil)), false)
             }
+        };
+        let condition = self.attempt(Self::assignment)?;
+        // a declaration takes in the whitespace after it, so the `;` is missed at the next token
+        if declared && condition.is_none() && !match_token!(self, [Semicolon]) {
+            let message = "Expected ';' after for loop condition";
+            return Err(self.error_at(message, self.peek().get_span().start()));
         }
-
-        Ok(Stmt::new_expression(expr))
+        consume!(self, Semicolon, "Expected ';' after for loop condition")?;
+        let increment = self.attempt(Self::assignment)?;
+        consume!(self, RightParen, "Expected ')' after for loop increment")?;
+        let body = self.body("Expected body after for loop clauses")?;
+
+        let span = keyword
+            .get_span()
+            .to(self.previous().map_or(keyword.get_span(), Token::get_span));
+        let increment =
+            increment.unwrap_or_else(|| Expr::new_literal(Token::new_token(TokenType::Nil, span)));
+        let condition =
+            condition.unwrap_or_else(|| Expr::new_literal(Token::new_token(TokenType::True, span)));
+
+        Ok(Stmt::new_block(
+            vec![
+                initializer,
+                Stmt::new_while(
+                    condition,
+                    Box::new(Stmt::new_block(
+                        vec![body, Stmt::new_expression(increment)],
+                        span,
+                    )),
+                ),
+            ],
+            span,
+        ))
     }
 
-    fn declaration(&mut self) -> Result<Stmt> {
+    fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        if match_token!(self, [Comment]) {
+            let comment = self.take_next();
+            return Ok(Stmt::new_expression(Expr::new_literal(Token::new_token(
+                TokenType::Nil,
+                comment.get_span(),
+            ))));
+        }
+
         if match_token!(self, [Var]) {
             self.take_next();
-            let name = self.take_next().unwrap();
-
-            if !matches!(name.get_token_type(), TokenType::Identifier(_)) {
-                self.m_errors.push(format!(
-                    "Expected identifier after 'var'\n    => line {} | column {}",
-                    name.get_line_number().saturating_sub(1),
-                    name.get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
-            }
-
-            let initializer = if match_token!(self, [Equal]) {
-                self.take_next();
-                Some(self.expression()?)
-            } else {
-                None
-            };
-
-            match self.take_next() {
-                Some(token) => {
-                    if token.get_token_type() == &TokenType::Semicolon {
-                    } else {
-                        self.m_errors.push(format!(
-                            "Expected ';' after variable declaration\n    => line {} | column {}",
-                            token.get_line_number().saturating_sub(1),
-                            token.get_col_range().start + 1
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
-                }
-                None => {
-                    self.m_errors.push(format!(
-                        "Expected ';' after variable declaration\n    => line {} | column {}",
-                        self.m_previous
-                            .as_ref()
-                            .unwrap()
-                            .get_line_number()
-                            .saturating_sub(1),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
+            let name = self.name("Expected variable name")?;
+            let initializer = match match_token!(self, [Equal]) {
+                true => {
+                    self.take_next();
+                    Some(self.expression("Expected expression after '='")?)
                 }
-            }
-
-            let mut statements = Vec::new();
-            while self.peek_next().is_some() && !match_token!(self, [RightBrace, Eof]) {
-                statements.push(self.declaration()?);
-            }
-            // self.take_next();
+                false => None,
+            };
+            consume!(self, Semicolon, "Expected ';' after variable declaration")?;
+            return Ok(Stmt::new_var(name, initializer));
+        }
 
-            return Ok(Stmt::new_var(name, initializer, statements));
+        // `fun (` starts an anonymous function instead
+        if match_token!(self, [Fun]) && self.peek_nth(1).get_token_type() != &TokenType::LeftParen {
+            self.take_next();
+            // the nom parser has looked past the whitespace for a `(`, so a missing name is
+            // reported at the next token
+            let next = self.peek().get_span().start();
+            let name =
+                self.name("Expected function name")
+                    .map_err(|err| match err.get_message() {
+                        "Expected function name" => self.error_at("Expected function name", next),
+                        _ => err,
+                    })?;
+            consume!(self, LeftParen, "Expected '(' after function name")?;
+            let parameters = self.parameters()?;
+            let body = self.block("Expected '{' before function body")?;
+            return Ok(Stmt::new_function(name, parameters, Box::new(body)));
         }
 
-        if multi_match_token!(self, [[Fun], [Identifier(_)]]) {
+        if match_token!(self, [Import]) {
             self.take_next();
+            let path = self.module_path("Expected module path after 'import'")?;
+            consume!(self, As, "Expected 'as' after module path")?;
+            let alias = self.name("Expected module name after 'as'")?;
+            consume!(self, Semicolon, "Expected ';' after import")?;
+            return Ok(Stmt::new_import(path, Some(alias), vec![]));
+        }
 
-            let name = if let TokenType::Identifier(_) = self.peek_next().unwrap().get_token_type()
+        if match_token!(self, [From]) {
+            self.take_next();
+            let path = self.module_path("Expected module path after 'from'")?;
+            consume!(self, Import, "Expected 'import' after module path")?;
+            let mut names = vec![self.name("Expected names to import")?];
+            // a `,` not followed by a name is left for the `;` to be missed at
+            while match_token!(self, [Comma])
+                && matches!(self.peek_nth(1).get_token_type(), TokenType::Identifier(_))
             {
-                self.take_next().unwrap()
-            } else {
-                self.m_errors.push(format!(
-                    "Expected identifier after 'fun'\n    => line {} | column {}",
-                    self.m_previous.as_ref().unwrap().get_line_number(),
-                    self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                ));
-                return Err(anyhow::anyhow!(""));
-            };
-
-            // if !matches!(name.get_token_type(), TokenType::Identifier(_)) {
-            //     self.m_errors.push(format!(
-            //         "Expected identifier after 'fun'\n    => line {} | column {}",
-            //         name.get_line_number().saturating_sub(1),
-            //         name.get_col_range().start + 1
-            //     ));
-            //     return Err(anyhow::anyhow!(""));
-            // }
-
-            if match_token!(self, [LeftParen]) {
                 self.take_next();
-                let mut parameters = Vec::new();
-                if !match_token!(self, [RightParen]) {
-                    loop {
-                        if parameters.len() >= 255 {
-                            self.m_errors.push(format!(
-                                "Cannot have more than 255 parameters\n    => line {} | column {}",
-                                self.m_previous.as_ref().unwrap().get_line_number(),
-                                self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                            ));
-                            return Err(anyhow::anyhow!(""));
-                        }
-
-                        parameters.push(self.take_next().unwrap());
-
-                        if !match_token!(self, [Comma]) {
-                            break;
-                        }
-
-                        self.take_next();
-                    }
-                }
+                names.push(self.take_next());
+            }
+            consume!(self, Semicolon, "Expected ';' after import")?;
+            return Ok(Stmt::new_import(path, None, names));
+        }
 
-                if match_token!(self, [RightParen]) {
-                    self.take_next();
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected ')' after function parameters\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
+        self.statement()
+    }
 
-                if match_token!(self, [LeftBrace]) {
-                    let body = self.statement()?;
-                    let body = match body {
-                        Stmt::Block { m_statements } => m_statements,
-                        _ => {
-                            self.m_errors.push(format!(
-                                "Expected block after function declaration\n    => line {} | column {}",
-                                self.m_previous.as_ref().unwrap().get_line_number(),
-                                self.m_previous.as_ref().unwrap().get_col_range().start + 1,
-                            ));
-                            return Err(anyhow::anyhow!(""));
-                        }
-                    };
-                    return Ok(Stmt::new_function(name, parameters, body));
-                } else {
-                    self.m_errors.push(format!(
-                        "Expected '{{' after function declaration\n    => line {} | column {}",
-                        self.m_previous.as_ref().unwrap().get_line_number(),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
-                }
-            } else {
-                self.m_errors.push(format!(
-                    "Expected '(' after function name\n    => line {} | column {}",
-                    name.get_line_number().saturating_sub(1),
-                    name.get_col_range().start
-                ));
-                return Err(anyhow::anyhow!(""));
+    /// Skips the rest of a statement that failed to parse: up to and including its `;`, the `}`
+    /// closing its body or the end of its line, or up to a line that starts a new statement or
+    /// the `}` closing the enclosing block.
+    fn sync(&mut self, at: usize) {
+        // an error at the end of a line, such as a missing `;`, leaves the next line to be parsed
+        let rest = self.m_source[at..].trim_start_matches([' ', '\t', '\r']);
+        if rest.starts_with('\n') {
+            let line_end = self.m_source.len() - rest.len();
+            while self.peek().get_span().start() <= line_end && !match_token!(self, [Eof]) {
+                self.take_next();
             }
+            return;
         }
 
-        if match_token!(self, [Return]) {
-            self.take_next();
-            let keyword = self.m_previous.clone().unwrap();
-            let value = if !match_token!(self, [Semicolon]) {
-                Some(self.expression()?)
-            } else {
-                None
-            };
+        let (mut braces, mut parens) = (0, 0);
+        loop {
+            // the token at the error itself does not count as starting a line
+            if braces == 0
+                && self.peek().get_span().start() > at
+                && self.starts_line()
+                && (parens == 0
+                    || match_token!(
+                        self,
+                        [Var, Fun, Import, From, If, While, For, Return, Print]
+                    ))
+            {
+                return;
+            }
 
-            match self.take_next() {
-                Some(token) => {
-                    if token.get_token_type() == &TokenType::Semicolon {
-                    } else {
-                        self.m_errors.push(format!(
-                            "Expected ';' after return value\n    => line {} | column {}",
-                            token.get_line_number().saturating_sub(1),
-                            token.get_col_range().start + 1
-                        ));
-                        return Err(anyhow::anyhow!(""));
-                    }
+            match self.peek().get_token_type() {
+                TokenType::Eof => return,
+                // an unclosed `(` is forgotten at a brace, so it does not swallow the rest
+                TokenType::LeftBrace => (braces, parens) = (braces + 1, 0),
+                TokenType::RightBrace if braces == 0 => return,
+                TokenType::RightBrace if braces == 1 => {
+                    self.take_next();
+                    return;
                 }
-                None => {
-                    self.m_errors.push(format!(
-                        "Expected ';' after return value\n    => line {} | column {}",
-                        self.m_previous
-                            .as_ref()
-                            .unwrap()
-                            .get_line_number()
-                            .saturating_sub(1),
-                        self.m_previous.as_ref().unwrap().get_col_range().start + 1
-                    ));
-                    return Err(anyhow::anyhow!(""));
+                TokenType::RightBrace => (braces, parens) = (braces - 1, 0),
+                TokenType::LeftParen => parens += 1,
+                TokenType::RightParen if parens > 0 => parens -= 1,
+                TokenType::Semicolon if braces == 0 && parens == 0 => {
+                    self.take_next();
+                    return;
                 }
+                _ => (),
             }
-
-            return Ok(Stmt::new_return(keyword, value));
+            self.take_next();
         }
-
-        self.statement()
     }
 
-    pub fn parse(mut self) -> Result<Vec<Stmt>, Vec<String>> {
-        let mut statements = Vec::new();
-        while self
-            .peek_next()
-            .is_some_and(|token| token.get_token_type() != &TokenType::Eof)
-        {
-            if let Ok(stmt) = self.declaration() {
-                statements.push(stmt);
-            } else {
-                self.sync();
+    pub fn parse(mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let statements = match self.declarations(false) {
+            Ok(statements) => statements,
+            Err(err) => {
+                self.report(err);
+                vec![]
             }
-        }
+        };
 
-        // dbg!(&statements);
-
-        if self.m_errors.is_empty() {
-            Ok(statements)
-        } else {
-            Err(self.m_errors)
+        match self.m_errors.is_empty() {
+            true => Ok(statements),
+            false => Err(self.m_errors),
         }
     }
 }
+
+pub(crate) fn parse_program(input: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    Parser::new(input, Lexer::new(input).tokenize()).parse()
+}
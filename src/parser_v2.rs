@@ -1,42 +1,233 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until, take_while, take_while1},
-    character::complete::{char, digit1, multispace0, multispace1},
-    combinator::{cut, eof, map, map_res, opt, peek},
-    error::{convert_error, VerboseError},
-    multi::{many0, many_till},
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::{char, digit1, multispace0, satisfy},
+    combinator::{consumed, cut, map, map_res, not, opt, peek, recognize},
+    error::{context, ErrorKind, ParseError as _, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
 use crate::ast_v2::*;
+use crate::compat::*;
 use crate::token_v2::*;
 
-type Token = TokenType;
+use itertools::Either;
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
 
-pub(crate) fn parse_identifier<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Token, VerboseError<&'a str>> {
-    pair(
+thread_local! {
+    // address of the source currently being parsed, used to turn the slices nom hands back into
+    // byte offsets for spans
+    static SOURCE_START: Cell<usize> = const { Cell::new(0) };
+    // syntax errors found so far in the source being parsed, each skipped over to carry on
+    static ERRORS: RefCell<Vec<(String, Span)>> = const { RefCell::new(Vec::new()) };
+}
+
+// the reason `parse_identifier` gives for refusing a keyword, which the error message follows
+pub(crate) const RESERVED_WORD: &str = "is a reserved word and cannot be used as a name";
+// the message for a failure no parser along the way gave a message for
+pub(crate) const EXPECTED_EXPRESSION: &str = "Expected expression";
+
+fn span_of(fragment: &str) -> Span {
+    let start = (fragment.as_ptr() as usize).wrapping_sub(SOURCE_START.with(Cell::get));
+    Span::new(start, start + fragment.len())
+}
+
+fn spanned<'a, O, F>(
+    parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (O, Span), VerboseError<&'a str>>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
+{
+    map(consumed(parser), |(fragment, output)| {
+        (output, span_of(fragment))
+    })
+}
+
+fn token<'a, O, F>(
+    token_type: TokenType,
+    parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Token, VerboseError<&'a str>>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
+{
+    map(spanned(parser), move |(_, span)| {
+        Token::new_token(token_type.clone(), span)
+    })
+}
+
+/// Matches `word` only as a whole word, so that `or` is not found at the start of `order`.
+fn keyword<'a>(
+    word: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, VerboseError<&'a str>> {
+    terminated(
+        tag(word),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    )
+}
+
+/// Requires `parser` to match after any whitespace, failing with `message` where it was expected.
+fn expect<'a, O, F>(
+    parser: F,
+    message: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
+{
+    context(message, cut(preceded(multispace0, parser)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    m_message: String,
+    m_span: Span,
+    m_line: usize,
+    m_column: usize,
+}
+
+impl ParseError {
+    pub fn new(message: String, span: Span, source: &str) -> ParseError {
+        let (line, column) = span.line_col(source);
+        ParseError {
+            m_message: message,
+            m_span: span,
+            m_line: line,
+            m_column: column,
+        }
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.m_message
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.m_span
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} => line {} | column {}",
+            self.m_message, self.m_line, self.m_column
+        )
+    }
+}
+
+pub(crate) fn parse_identifier(input: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    // the `r` of a raw string is not a name
+    if StringLiteral::scan(input).is_some() {
+        return Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Alpha,
+        )));
+    }
+
+    let (rest, (ident, span)) = spanned(recognize(pair(
         take_while1(|c: char| c.is_alphabetic() || c == '_'),
         take_while(|c: char| c.is_alphanumeric() || c == '_'),
-    )(input)
-    .map(|(input, (ident0, ident1))| {
-        (
+    )))(input)?;
+
+    if is_keyword(ident) {
+        return Err(nom::Err::Error(VerboseError {
+            errors: vec![(input, VerboseErrorKind::Context(RESERVED_WORD))],
+        }));
+    }
+
+    Ok((
+        rest,
+        Token::new_token(TokenType::new_identifier(ident), span),
+    ))
+}
+
+/// A string literal. An invalid escape sequence in it is recorded without failing, as it does not
+/// stop the rest of the program from being parsed.
+pub(crate) fn parse_string(input: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    scan_string(input).map(|(rest, (token, _))| (rest, token))
+}
+
+/// A string literal as a token, with what it is made of.
+fn scan_string(input: &str) -> IResult<&str, (Token, StringLiteral), VerboseError<&str>> {
+    let Some(literal) = StringLiteral::scan(input) else {
+        return Err(nom::Err::Error(VerboseError::from_error_kind(
             input,
-            Token::new_identifier(format!("{}{}", ident0, ident1).as_str()),
-        )
-    })
+            ErrorKind::Char,
+        )));
+    };
+    let Some(end) = literal.get_end() else {
+        let body = &input[literal.get_body_start()..];
+        return Err(nom::Err::Failure(VerboseError {
+            errors: vec![(body, VerboseErrorKind::Context("Unterminated string"))],
+        }));
+    };
+
+    for (message, span) in literal.get_errors() {
+        record(message.clone(), span_of(&input[span.start()..span.end()]));
+    }
+    let token_type = TokenType::String(literal.get_value().into());
+    Ok((
+        &input[end..],
+        (
+            Token::new_token(token_type, span_of(&input[..end])),
+            literal,
+        ),
+    ))
 }
 
-pub(crate) fn parse_string<'a>(input: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
-    delimited(char('\"'), take_until("\""), cut(char('\"')))(input)
-        .map(|(input, string)| (input, Token::String(string.to_string())))
+/// A string literal, which is an interpolation when it embeds expressions with `${...}`.
+pub(crate) fn parse_text(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    let (rest, (token, literal)) = scan_string(input)?;
+    if !literal.is_interpolated() {
+        return Ok((rest, Expr::new_literal(token)));
+    }
+
+    let parts = literal
+        .get_parts()
+        .iter()
+        .map(|part| match part {
+            StringPart::Text(text, span) => Expr::new_literal(Token::new_token(
+                TokenType::String(text.clone()),
+                span_of(&input[span.start()..span.end()]),
+            )),
+            StringPart::Code(span) => parse_embedded(&input[span.start()..span.end()]),
+        })
+        .collect();
+    Ok((rest, Expr::new_interpolation(parts, token.get_span())))
 }
 
-pub(crate) fn parse_number<'a>(input: &'a str) -> IResult<&'a str, Token, VerboseError<&'a str>> {
-    map_res(
-        pair(digit1, opt(preceded(char('.'), cut(digit1)))),
+/// The expression embedded in a string by `${...}`. A mistake in it is recorded without failing,
+/// like an invalid escape sequence, and leaves `nil` in its place.
+fn parse_embedded(code: &str) -> Expr {
+    let err = match parse_expression(code) {
+        Ok((rest, expr)) if rest.trim_start_matches([' ', '\t', '\r', '\n']).is_empty() => {
+            return expr
+        }
+        Ok((rest, _)) => VerboseError {
+            errors: vec![(
+                rest,
+                VerboseErrorKind::Context("Expected '}' after expression"),
+            )],
+        },
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => err,
+        Err(nom::Err::Incomplete(_)) => VerboseError::from_error_kind(code, ErrorKind::Eof),
+    };
+
+    record_error(err, code);
+    Expr::new_literal(Token::new_token(TokenType::Nil, span_of(code)))
+}
+
+pub(crate) fn parse_number(input: &str) -> IResult<&str, Token, VerboseError<&str>> {
+    spanned(map_res(
+        pair(
+            digit1,
+            opt(preceded(
+                char('.'),
+                context("Expected digits after '.'", cut(digit1)),
+            )),
+        ),
         |(int, dec): (&str, Option<&str>)| {
             let mut num = int.to_string();
             if let Some(dec) = dec {
@@ -45,38 +236,33 @@ pub(crate) fn parse_number<'a>(input: &'a str) -> IResult<&'a str, Token, Verbos
             }
             num.parse::<f64>()
         },
-    )(input)
-    .map(|(input, number)| (input, Token::Number(number)))
+    ))(input)
+    .map(|(input, (number, span))| (input, Token::new_token(TokenType::Number(number), span)))
 }
 
-pub(crate) fn parse_anonymous_function<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_anonymous_function(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     map(
         tuple((
-            preceded(multispace0, tag("fun")),
-            preceded(multispace0, char('(')),
+            preceded(multispace0, keyword("fun")),
+            expect(char('('), "Expected '(' after 'fun'"),
             preceded(multispace0, opt(parse_parameters)),
-            preceded(multispace0, cut(char(')'))),
-            preceded(multispace0, cut(parse_block)),
+            expect(char(')'), "Expected ')' after parameters"),
+            expect(parse_block, "Expected '{' before function body"),
         )),
         |(_, _, params, _, body)| Expr::new_function(params.unwrap_or(vec![]), Box::new(body)),
     )(input)
 }
 
-pub(crate) fn parse_arguments<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Vec<Expr>, VerboseError<&'a str>> {
-    let (input, (first, (tail, _))) = pair(
-        opt(parse_expression),
-        many_till(
-            preceded(
-                preceded(multispace0, char(',')),
-                preceded(multispace0, parse_expression),
-            ),
-            peek(preceded(multispace0, char(')'))),
-        ),
-    )(input)?;
+pub(crate) fn parse_arguments(input: &str) -> IResult<&str, Vec<Expr>, VerboseError<&str>> {
+    let (input, first) = opt(parse_expression)(input)?;
+    if first.is_none() {
+        return Ok((input, vec![]));
+    }
+
+    let (input, tail) = many0(preceded(
+        preceded(multispace0, char(',')),
+        expect(parse_expression, "Expected expression after ','"),
+    ))(input)?;
     Ok((
         input,
         first.map_or(vec![], |expr| {
@@ -87,104 +273,119 @@ pub(crate) fn parse_arguments<'a>(
     ))
 }
 
-pub(crate) fn parse_parameters<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Vec<Token>, VerboseError<&'a str>> {
+pub(crate) fn parse_parameters(input: &str) -> IResult<&str, Vec<Token>, VerboseError<&str>> {
     pair(
-        parse_identifier,
-        many_till(
-            preceded(
-                preceded(multispace0, char(',')),
-                preceded(multispace0, parse_identifier),
-            ),
-            peek(preceded(multispace0, char(')'))),
+        preceded(
+            not(char(')')),
+            expect(parse_identifier, "Expected parameter name"),
         ),
+        many0(preceded(
+            preceded(multispace0, char(',')),
+            expect(parse_identifier, "Expected parameter name after ','"),
+        )),
     )(input)
-    .map(|(input, (first, (tail, _)))| {
-        (
-            input,
-            vec![first].into_iter().chain(tail.into_iter()).collect(),
-        )
-    })
+    .map(|(input, (first, tail))| (input, vec![first].into_iter().chain(tail).collect()))
 }
 
-pub(crate) fn parse_comment<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_comment(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
         preceded(
             multispace0,
-            preceded(
+            spanned(preceded(
                 tag("//"),
-                terminated(take_while(|c: char| c != '\n'), char('\n')),
-            ),
+                terminated(take_while(|c: char| c != '\n'), opt(char('\n'))),
+            )),
         ),
-        |_| Stmt::new_expression(Expr::new_literal(Token::Nil)),
+        |(_, span)| Stmt::new_expression(Expr::new_literal(Token::new_token(TokenType::Nil, span))),
     )(input)
 }
 
-pub(crate) fn parse_primary<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_primary(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     alt((
-        map(tag("true"), |_| Expr::new_literal(Token::True)),
-        map(tag("false"), |_| Expr::new_literal(Token::False)),
-        map(tag("nil"), |_| Expr::new_literal(Token::Nil)),
+        map(token(TokenType::True, keyword("true")), Expr::new_literal),
+        map(token(TokenType::False, keyword("false")), Expr::new_literal),
+        map(token(TokenType::Nil, keyword("nil")), Expr::new_literal),
         map(parse_number, Expr::new_literal),
-        map(parse_string, Expr::new_literal),
+        parse_text,
         parse_anonymous_function,
         map(parse_identifier, Expr::new_variable),
-        map(delimited(char('('), parse_expression, cut(char(')'))), |expr| {
-            Expr::new_grouping(Box::new(expr))
-        }),
+        map(
+            delimited(
+                char('('),
+                expect(parse_expression, "Expected expression after '('"),
+                expect(char(')'), "Expected ')' after expression"),
+            ),
+            |expr| Expr::new_grouping(Box::new(expr)),
+        ),
     ))(input)
 }
 
-pub(crate) fn parse_call<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_call(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         preceded(multispace0, parse_primary),
-        many0(delimited(
-            preceded(multispace0, char('(')),
-            parse_arguments,
-            preceded(multispace0, cut(char(')'))),
-        )),
+        many0(alt((
+            map(
+                delimited(
+                    preceded(multispace0, char('(')),
+                    parse_arguments,
+                    expect(char(')'), "Expected ')' after arguments"),
+                ),
+                Either::Left,
+            ),
+            map(
+                preceded(
+                    preceded(multispace0, char('.')),
+                    expect(parse_identifier, "Expected property name after '.'"),
+                ),
+                Either::Right,
+            ),
+        ))),
     )(input)
-    .map(|(input, (callee, arguments))| {
+    .map(|(input, (callee, suffixes))| {
         (
             input,
-            arguments.into_iter().fold(callee, |callee, arguments| {
-                Expr::new_call(Box::new(callee), arguments)
-            }),
+            suffixes
+                .into_iter()
+                .fold(callee, |callee, suffix| match suffix {
+                    Either::Left(arguments) => Expr::new_call(Box::new(callee), arguments),
+                    Either::Right(name) => Expr::new_get(Box::new(callee), name),
+                }),
         )
     })
 }
 
-pub(crate) fn parse_unary<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_unary(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     alt((
         map(
             pair(
-                alt((char('!'), char('-'))),
+                preceded(
+                    multispace0,
+                    alt((
+                        token(TokenType::Bang, char('!')),
+                        token(TokenType::Minus, char('-')),
+                    )),
+                ),
                 preceded(multispace0, parse_unary),
             ),
-            |(op, expr)| match op {
-                '!' => Expr::new_unary(Token::Bang, Box::new(expr)),
-                '-' => Expr::new_unary(Token::Minus, Box::new(expr)),
-                _ => unreachable!(),
-            },
+            |(op, expr)| Expr::new_unary(op, Box::new(expr)),
         ),
         parse_call,
     ))(input)
 }
 
-pub(crate) fn parse_factor<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_factor(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_unary,
-        many0(map(
-            pair(
-                preceded(multispace0, alt((char('*'), char('/')))),
-                preceded(multispace0, cut(parse_unary)),
+        many0(pair(
+            preceded(
+                multispace0,
+                alt((
+                    token(TokenType::Star, char('*')),
+                    // `//` starts a comment rather than a division
+                    token(TokenType::Slash, terminated(char('/'), not(char('/')))),
+                )),
             ),
-            |(op, expr)| match op {
-                '*' => (Token::Star, expr),
-                '/' => (Token::Slash, expr),
-                _ => unreachable!(),
-            },
+            expect(parse_unary, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
@@ -197,19 +398,18 @@ pub(crate) fn parse_factor<'a>(input: &'a str) -> IResult<&'a str, Expr, Verbose
     })
 }
 
-pub(crate) fn parse_term<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_term(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_factor,
-        many0(map(
-            pair(
-                preceded(multispace0, alt((char('+'), char('-')))),
-                preceded(multispace0, cut(parse_factor)),
+        many0(pair(
+            preceded(
+                multispace0,
+                alt((
+                    token(TokenType::Plus, char('+')),
+                    token(TokenType::Minus, char('-')),
+                )),
             ),
-            |(op, expr)| match op {
-                '+' => (Token::Plus, expr),
-                '-' => (Token::Minus, expr),
-                _ => unreachable!(),
-            },
+            expect(parse_factor, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
@@ -222,23 +422,20 @@ pub(crate) fn parse_term<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseEr
     })
 }
 
-pub(crate) fn parse_comparison<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_comparison(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_term,
-        many0(map(
-            pair(
-                preceded(multispace0, alt((tag(">="), tag(">"), tag("<="), tag("<")))),
-                preceded(multispace0, cut(parse_term)),
+        many0(pair(
+            preceded(
+                multispace0,
+                alt((
+                    token(TokenType::GreaterEqual, tag(">=")),
+                    token(TokenType::Greater, tag(">")),
+                    token(TokenType::LessEqual, tag("<=")),
+                    token(TokenType::Less, tag("<")),
+                )),
             ),
-            |(op, expr)| match op {
-                ">" => (Token::Greater, expr),
-                ">=" => (Token::GreaterEqual, expr),
-                "<" => (Token::Less, expr),
-                "<=" => (Token::LessEqual, expr),
-                _ => unreachable!(),
-            },
+            expect(parse_term, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
@@ -251,19 +448,18 @@ pub(crate) fn parse_comparison<'a>(
     })
 }
 
-pub(crate) fn parse_equality<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_equality(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_comparison,
-        many0(map(
-            pair(
-                preceded(multispace0, alt((tag("!="), tag("==")))),
-                preceded(multispace0, cut(parse_comparison)),
+        many0(pair(
+            preceded(
+                multispace0,
+                alt((
+                    token(TokenType::BangEqual, tag("!=")),
+                    token(TokenType::EqualEqual, tag("==")),
+                )),
             ),
-            |(op, expr)| match op {
-                "!=" => (Token::BangEqual, expr),
-                "==" => (Token::EqualEqual, expr),
-                _ => unreachable!(),
-            },
+            expect(parse_comparison, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
@@ -276,51 +472,43 @@ pub(crate) fn parse_equality<'a>(input: &'a str) -> IResult<&'a str, Expr, Verbo
     })
 }
 
-pub(crate) fn parse_logic_and<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_logic_and(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_equality,
-        many0(map(
-            pair(
-                preceded(multispace0, tag("and")),
-                preceded(multispace0, cut(parse_equality)),
-            ),
-            |(_, expr)| expr,
+        many0(pair(
+            preceded(multispace0, token(TokenType::And, keyword("and"))),
+            expect(parse_equality, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
         (
             input,
-            tail.into_iter().fold(first, |left, right| {
-                Expr::new_logical(Box::new(left), Token::And, Box::new(right))
+            tail.into_iter().fold(first, |left, (op, right)| {
+                Expr::new_logical(Box::new(left), op, Box::new(right))
             }),
         )
     })
 }
 
-pub(crate) fn parse_logic_or<'a>(input: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_logic_or(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     pair(
         parse_logic_and,
-        many0(map(
-            pair(
-                preceded(multispace0, tag("or")),
-                preceded(multispace0, cut(parse_logic_and)),
-            ),
-            |(_, expr)| expr,
+        many0(pair(
+            preceded(multispace0, token(TokenType::Or, keyword("or"))),
+            expect(parse_logic_and, "Expected expression after operator"),
         )),
     )(input)
     .map(|(input, (first, tail))| {
         (
             input,
-            tail.into_iter().fold(first, |left, right| {
-                Expr::new_logical(Box::new(left), Token::Or, Box::new(right))
+            tail.into_iter().fold(first, |left, (op, right)| {
+                Expr::new_logical(Box::new(left), op, Box::new(right))
             }),
         )
     })
 }
 
-pub(crate) fn parse_assignment<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_assignment(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     alt((
         map(
             separated_pair(
@@ -334,58 +522,88 @@ pub(crate) fn parse_assignment<'a>(
     ))(input)
 }
 
-pub(crate) fn parse_expression<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+pub(crate) fn parse_expression(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     parse_assignment(input)
 }
 
-pub(crate) fn parse_block<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_block(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     preceded(
-        preceded(multispace0, char('{')),
-        many_till(
-            preceded(multispace0, parse_declaration),
-            preceded(multispace0, char('}')),
-        ),
+        multispace0,
+        spanned(preceded(char('{'), |input| parse_declarations(input, true))),
+    )(input)
+    .map(|(input, (stmts, span))| (input, Stmt::new_block(stmts, span)))
+}
+
+/// The body of `if`, `while` and `for`, which must be a block unless following clox.
+pub(crate) fn parse_body(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
+    match dialect() {
+        Dialect::Rlox => parse_block(input),
+        Dialect::Clox => parse_statement(input),
+    }
+}
+
+/// clox's `print` statement, which becomes a call to the `println` native.
+pub(crate) fn parse_print(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
+    if dialect() != Dialect::Clox {
+        return Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        )));
+    }
+
+    map(
+        tuple((
+            preceded(multispace0, spanned(keyword("print"))),
+            expect(parse_expression, "Expected expression after 'print'"),
+            expect(char(';'), "Expected ';' after value"),
+        )),
+        |((_, span), value, _)| {
+            let println = Token::new_token(TokenType::new_identifier("println"), span);
+            Stmt::new_expression(Expr::new_call(
+                Box::new(Expr::new_variable(println)),
+                vec![value],
+            ))
+        },
     )(input)
-    .map(|(input, (stmts, _))| (input, Stmt::new_block(stmts)))
 }
 
-pub(crate) fn parse_while<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_while(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
         tuple((
-            preceded(multispace0, tag("while")),
-            preceded(multispace1, cut(char('('))),
-            preceded(multispace0, cut(parse_expression)),
-            preceded(multispace0, cut(char(')'))),
-            preceded(multispace0, cut(parse_block)),
+            preceded(multispace0, keyword("while")),
+            expect(char('('), "Expected '(' after 'while'"),
+            expect(parse_expression, "Expected condition after '('"),
+            expect(char(')'), "Expected ')' after while condition"),
+            expect(parse_body, "Expected body after while condition"),
         )),
         |(_, _, condition, _, body)| Stmt::new_while(condition, Box::new(body)),
     )(input)
 }
 
-pub(crate) fn parse_return<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_return(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
-        delimited(
-            preceded(multispace0, tag("return")),
-            opt(preceded(multispace1, parse_expression)),
-            preceded(multispace0, cut(char(';'))),
+        terminated(
+            pair(
+                preceded(multispace0, token(TokenType::Return, keyword("return"))),
+                opt(preceded(multispace0, parse_expression)),
+            ),
+            expect(char(';'), "Expected ';' after return value"),
         ),
-        Stmt::new_return,
+        |(keyword, value)| Stmt::new_return(keyword, value),
     )(input)
 }
 
-pub(crate) fn parse_if<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_if(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
         tuple((
-            preceded(multispace0, tag("if")),
-            preceded(multispace1, cut(char('('))),
-            preceded(multispace0, cut(parse_expression)),
-            preceded(multispace0, cut(char(')'))),
-            preceded(multispace0, cut(parse_block)),
+            preceded(multispace0, keyword("if")),
+            expect(char('('), "Expected '(' after 'if'"),
+            expect(parse_expression, "Expected condition after '('"),
+            expect(char(')'), "Expected ')' after if condition"),
+            expect(parse_body, "Expected body after if condition"),
             opt(preceded(
-                preceded(multispace0, tag("else")),
-                cut(parse_block),
+                preceded(multispace0, keyword("else")),
+                expect(parse_body, "Expected body after 'else'"),
             )),
         )),
         |(_, _, condition, _, then_branch, else_branch)| {
@@ -394,54 +612,72 @@ pub(crate) fn parse_if<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseErro
     )(input)
 }
 
-pub(crate) fn parse_for<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_for(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
-        tuple((
-            preceded(multispace0, tag("for")),
-            preceded(multispace1, cut(char('('))),
-            preceded(
-                multispace0,
-                alt((
-                    parse_declaration,
-                    parse_expression_stmt,
-                    map(cut(char(';')), |_| {
-                        Stmt::new_expression(Expr::new_literal(Token::Nil))
-                    }),
-                )),
-            ),
-            opt(preceded(multispace0, parse_expression)),
-            preceded(multispace0, cut(char(';'))),
-            opt(preceded(multispace0, parse_expression)),
-            preceded(multispace0, cut(char(')'))),
-            preceded(multispace0, cut(parse_block)),
-        )),
-        |(_, _, initializer, condition, _, increment, _, body)| {
-            Stmt::new_block(vec![
-                initializer,
-                Stmt::new_while(
-                    condition.unwrap_or(Expr::new_literal(Token::True)),
-                    Box::new(Stmt::new_block(vec![
-                        body,
-                        Stmt::new_expression(increment.unwrap_or(Expr::new_literal(Token::Nil))),
-                    ])),
+        preceded(
+            multispace0,
+            spanned(tuple((
+                keyword("for"),
+                expect(char('('), "Expected '(' after 'for'"),
+                preceded(
+                    multispace0,
+                    alt((
+                        parse_declaration,
+                        parse_expression_stmt,
+                        map(
+                            expect(
+                                token(TokenType::Nil, char(';')),
+                                "Expected for loop initializer or ';'",
+                            ),
+                            |token| Stmt::new_expression(Expr::new_literal(token)),
+                        ),
+                    )),
                 ),
-            ])
+                opt(preceded(multispace0, parse_expression)),
+                expect(char(';'), "Expected ';' after for loop condition"),
+                opt(preceded(multispace0, parse_expression)),
+                expect(char(')'), "Expected ')' after for loop increment"),
+                expect(parse_body, "Expected body after for loop clauses"),
+            ))),
+        ),
+        |((_, _, initializer, condition, _, increment, _, body), span)| {
+            Stmt::new_block(
+                vec![
+                    initializer,
+                    Stmt::new_while(
+                        condition
+                            .unwrap_or(Expr::new_literal(Token::new_token(TokenType::True, span))),
+                        Box::new(Stmt::new_block(
+                            vec![
+                                body,
+                                Stmt::new_expression(increment.unwrap_or(Expr::new_literal(
+                                    Token::new_token(TokenType::Nil, span),
+                                ))),
+                            ],
+                            span,
+                        )),
+                    ),
+                ],
+                span,
+            )
         },
     )(input)
 }
 
-pub(crate) fn parse_expression_stmt<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_expression_stmt(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
-        terminated(parse_expression, preceded(multispace0, cut(char(';')))),
+        terminated(
+            parse_expression,
+            expect(char(';'), "Expected ';' after expression"),
+        ),
         Stmt::new_expression,
     )(input)
 }
 
-pub(crate) fn parse_statement<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_statement(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     alt((
         parse_block,
+        parse_print,
         parse_while,
         parse_return,
         parse_if,
@@ -450,56 +686,298 @@ pub(crate) fn parse_statement<'a>(input: &'a str) -> IResult<&'a str, Stmt, Verb
     ))(input)
 }
 
-pub(crate) fn parse_var<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_var(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
         tuple((
-            preceded(multispace0, tag("let")),
-            preceded(multispace1, parse_identifier),
+            preceded(multispace0, keyword(declaration_keyword())),
+            expect(parse_identifier, "Expected variable name"),
             opt(preceded(
-                preceded(multispace0, char('=')),
-                preceded(multispace0, cut(parse_expression)),
+                preceded(multispace0, terminated(char('='), not(char('=')))),
+                expect(parse_expression, "Expected expression after '='"),
             )),
-            preceded(multispace0, cut(char(';'))),
+            expect(char(';'), "Expected ';' after variable declaration"),
         )),
         |(_, name, initializer, _)| Stmt::new_var(name, initializer),
     )(input)
 }
 
-pub(crate) fn parse_function<'a>(input: &'a str) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+/// `import "path" as name;` or `from "path" import a, b;`.
+pub(crate) fn parse_import(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
+    alt((
+        map(
+            tuple((
+                preceded(multispace0, keyword("import")),
+                expect(parse_string, "Expected module path after 'import'"),
+                expect(keyword("as"), "Expected 'as' after module path"),
+                expect(parse_identifier, "Expected module name after 'as'"),
+                expect(char(';'), "Expected ';' after import"),
+            )),
+            |(_, path, _, alias, _)| Stmt::new_import(path, Some(alias), vec![]),
+        ),
+        map(
+            tuple((
+                preceded(multispace0, keyword("from")),
+                expect(parse_string, "Expected module path after 'from'"),
+                expect(keyword("import"), "Expected 'import' after module path"),
+                expect(
+                    separated_list1(
+                        preceded(multispace0, char(',')),
+                        preceded(multispace0, parse_identifier),
+                    ),
+                    "Expected names to import",
+                ),
+                expect(char(';'), "Expected ';' after import"),
+            )),
+            |(_, path, _, names, _)| Stmt::new_import(path, None, names),
+        ),
+    ))(input)
+}
+
+pub(crate) fn parse_function(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     map(
         tuple((
-            preceded(multispace0, tag("fun")),
-            preceded(multispace1, parse_identifier),
-            preceded(multispace0, cut(char('('))),
+            preceded(multispace0, keyword("fun")),
+            // `fun (` starts an anonymous function instead
+            preceded(multispace0, not(char('('))),
+            expect(parse_identifier, "Expected function name"),
+            expect(char('('), "Expected '(' after function name"),
             preceded(multispace0, opt(parse_parameters)),
-            preceded(multispace0, cut(char(')'))),
-            preceded(multispace0, cut(parse_block)),
+            expect(char(')'), "Expected ')' after parameters"),
+            expect(parse_block, "Expected '{' before function body"),
         )),
-        |(_, name, _, params, _, body)| {
+        |(_, _, name, _, params, _, body)| {
             Stmt::new_function(name, params.unwrap_or(vec![]), Box::new(body))
         },
     )(input)
 }
 
-pub(crate) fn parse_declaration<'a>(
-    input: &'a str,
-) -> IResult<&'a str, Stmt, VerboseError<&'a str>> {
+pub(crate) fn parse_declaration(input: &str) -> IResult<&str, Stmt, VerboseError<&str>> {
     delimited(
         multispace0,
-        alt((parse_var, parse_function, parse_statement, parse_comment)),
+        alt((
+            parse_var,
+            parse_function,
+            parse_import,
+            parse_statement,
+            parse_comment,
+        )),
         multispace0,
     )(input)
 }
 
-pub(crate) fn parse_program<'a>(input: &'a str) -> Result<Vec<Stmt>, ()> {
-    let output = many_till(parse_declaration, preceded(multispace0, eof))(input);
+/// Skips a `#!` line at the start of a script, leaving its newline so spans and line numbers
+/// still count it.
+fn skip_shebang(input: &str) -> &str {
+    match input.starts_with("#!") {
+        true => &input[input.find('\n').unwrap_or(input.len())..],
+        false => input,
+    }
+}
+
+/// Whether `input` starts with a keyword that begins a statement, where parsing can resume.
+fn starts_statement(input: &str) -> bool {
+    let word = input
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(input, |end| &input[..end]);
 
-    match output {
-        Ok((_, (stmts, _))) => Ok(stmts),
-        Err(err) => {
-            println!("ERROR: ");
-            err.map(|err| println!("{}", convert_error(input, err)));
-            Err(())
+    ["fun", "import", "from", "if", "while", "for", "return"].contains(&word)
+        || dialect_keywords().contains(&word)
+}
+
+/// Skips the rest of a statement that failed to parse: up to and including its `;`, the `}`
+/// closing its body or the end of its line, or up to a line that starts a new statement or the
+/// `}` closing the enclosing block.
+fn synchronize(input: &str) -> &str {
+    // an error at the end of a line, such as a missing `;`, leaves the next line to be parsed
+    if let Some(next_line) = input
+        .trim_start_matches([' ', '\t', '\r'])
+        .strip_prefix('\n')
+    {
+        return next_line;
+    }
+
+    let (mut braces, mut parens) = (0, 0);
+    let mut line_start = false;
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if line_start && braces == 0 && starts_statement(&input[i..]) {
+            return &input[i..];
+        }
+
+        if let Some(literal) = StringLiteral::scan(&input[i..]) {
+            let Some(end) = literal.get_end() else {
+                return &input[input.len()..];
+            };
+            while chars.next_if(|&(j, _)| j < i + end).is_some() {}
+            line_start = false;
+            continue;
+        }
+
+        match c {
+            // a name is skipped whole, so an `r` ending it does not start a raw string
+            c if c.is_alphanumeric() || c == '_' => {
+                while chars
+                    .next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                    .is_some()
+                {}
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '/') => {
+                let line_end = chars.by_ref().find(|&(_, c)| c == '\n');
+                if let Some((j, _)) = line_end.filter(|_| braces == 0 && parens == 0) {
+                    return &input[j + 1..];
+                }
+                line_start = true;
+                continue;
+            }
+            '\n' if braces == 0 && parens == 0 => return &input[i + 1..],
+            // an unclosed `(` is forgotten at a brace, so it does not swallow the rest of the file
+            '{' => (braces, parens) = (braces + 1, 0),
+            '}' if braces == 0 => return &input[i..],
+            '}' if braces == 1 => return &input[i + 1..],
+            '}' => (braces, parens) = (braces - 1, 0),
+            '(' => parens += 1,
+            ')' if parens > 0 => parens -= 1,
+            ';' if braces == 0 && parens == 0 => return &input[i + 1..],
+            _ => (),
+        }
+        line_start = match c {
+            '\n' => true,
+            c if c.is_whitespace() => line_start,
+            _ => false,
+        };
+    }
+
+    &input[input.len()..]
+}
+
+/// Whether `c` can begin a token, or is whitespace between them.
+pub(crate) fn starts_token(c: char) -> bool {
+    c.is_alphanumeric() || " \t\r\n_(){},.-+;*/!=<>\"".contains(c)
+}
+
+fn record(message: String, span: Span) {
+    ERRORS.with(|errors| {
+        let mut errors = errors.borrow_mut();
+        // a block inside an alternative that was backtracked over reports its errors again
+        if !errors.contains(&(message.clone(), span)) {
+            errors.push((message, span));
+        }
+    });
+}
+
+/// Records the syntax error in `err` and returns where it happened. The message is the innermost
+/// `expect` that failed, and the location skips spaces but not newlines, so a missing `;` is
+/// reported at the end of its line.
+fn record_error<'a>(err: VerboseError<&'a str>, input: &'a str) -> &'a str {
+    let (at, message) = err
+        .errors
+        .iter()
+        .find_map(|(at, kind)| match kind {
+            VerboseErrorKind::Context(message) => Some((*at, *message)),
+            _ => None,
+        })
+        .unwrap_or_else(|| {
+            let at = err.errors.first().map_or(input, |(at, _)| *at);
+            (at, EXPECTED_EXPRESSION)
+        });
+
+    let at = at.trim_start_matches([' ', '\t']);
+    let error = match message {
+        RESERVED_WORD => {
+            let word = at
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(at, |end| &at[..end]);
+            (format!("'{}' {}", word, message), span_of(word))
+        }
+        message => {
+            let width = at.chars().next().map_or(0, char::len_utf8);
+            let message = match at.chars().next() {
+                // a character no token starts with is the mistake, whatever was expected there
+                Some(c) if !starts_token(c) => format!("Unexpected character '{}'", c),
+                _ => message.to_string(),
+            };
+            (message, span_of(&at[..width]))
+        }
+    };
+    let unterminated = message == "Unterminated string";
+    record(error.0, error.1);
+
+    // the rest of the source is inside the string, so there is nothing left to parse
+    match unterminated {
+        true => &at[at.len()..],
+        false => at,
+    }
+}
+
+/// Parses declarations up to the end of the input, or the `}` ending a block. A declaration that
+/// fails is recorded and skipped, so that one mistake does not hide the ones after it.
+fn parse_declarations(
+    mut input: &str,
+    in_block: bool,
+) -> IResult<&str, Vec<Stmt>, VerboseError<&str>> {
+    let mut stmts = Vec::new();
+    loop {
+        let (rest, _) = multispace0(input)?;
+        match rest.chars().next() {
+            None if in_block => {
+                return Err(nom::Err::Failure(VerboseError {
+                    errors: vec![(rest, VerboseErrorKind::Context("Expected '}' after block"))],
+                }))
+            }
+            None => return Ok((rest, stmts)),
+            Some('}') if in_block => return Ok((&rest[1..], stmts)),
+            Some('}') => {
+                let err = VerboseError {
+                    errors: vec![(rest, VerboseErrorKind::Context("Unexpected '}'"))],
+                };
+                input = &record_error(err, rest)[1..];
+                continue;
+            }
+            _ => (),
+        }
+
+        match parse_declaration(rest) {
+            Ok((rest, stmt)) => {
+                stmts.push(stmt);
+                input = rest;
+            }
+            Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+                input = synchronize(record_error(err, rest));
+                // always move on, even when the error is where the declaration started
+                if input.len() == rest.len() {
+                    input = &rest[rest.chars().next().map_or(0, char::len_utf8)..];
+                }
+            }
+            Err(nom::Err::Incomplete(_)) => return Ok(("", stmts)),
+        }
+    }
+}
+
+pub(crate) fn parse_program(input: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let previous = SOURCE_START.with(|start| start.replace(input.as_ptr() as usize));
+    let previous_errors = ERRORS.with(|errors| errors.take());
+    let source = skip_shebang(input);
+    let stmts = match parse_declarations(source, false) {
+        Ok((_, stmts)) => stmts,
+        // nothing reaches the top level uncaught, but if something does it is still an error
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+            record_error(err, source);
+            vec![]
+        }
+        Err(nom::Err::Incomplete(_)) => {
+            record_error(VerboseError { errors: vec![] }, &source[source.len()..]);
+            vec![]
         }
+    };
+    let errors = ERRORS.with(|errors| errors.replace(previous_errors));
+    SOURCE_START.with(|start| start.set(previous));
+
+    match errors.is_empty() {
+        true => Ok(stmts),
+        false => Err(errors
+            .into_iter()
+            .map(|(message, span)| ParseError::new(message, span, input))
+            .collect()),
     }
 }
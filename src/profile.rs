@@ -0,0 +1,194 @@
+use crate::hook::*;
+use crate::module::file_name;
+use crate::token_v2::Span;
+use crate::value::*;
+
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone)]
+pub struct FunctionStats {
+    m_calls: usize,
+    m_inclusive: Duration,
+    m_exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct ProfileFrame {
+    m_function: Function,
+    m_start: Instant,
+    m_children: Duration,
+}
+
+/// A Lox function told apart by the module and span it is declared at, since several can share a
+/// name. `None` is the top-level script.
+type Function = Option<(Option<PathBuf>, Span)>;
+
+/// Measures the time spent in every Lox function. Inclusive time covers the whole call,
+/// exclusive time leaves out the calls it made to other Lox functions.
+#[derive(Debug)]
+pub struct Profiler {
+    m_sources: Sources,
+    m_frames: Vec<ProfileFrame>,
+    // the name of each function and where it is declared, as `line` or `file:line`
+    m_names: HashMap<Function, (String, String)>,
+    m_stats: HashMap<Function, FunctionStats>,
+    m_stacks: HashMap<Vec<Function>, Duration>,
+}
+
+impl Profiler {
+    pub fn new(source: String) -> Profiler {
+        Profiler {
+            m_sources: Sources::new(source),
+            m_frames: vec![ProfileFrame {
+                m_function: None,
+                m_start: Instant::now(),
+                m_children: Duration::ZERO,
+            }],
+            m_names: HashMap::from([(None, ("<script>".into(), String::new()))]),
+            m_stats: HashMap::new(),
+            m_stacks: HashMap::new(),
+        }
+    }
+
+    fn enter(&mut self, function: Function) {
+        self.m_frames.push(ProfileFrame {
+            m_function: function,
+            m_start: Instant::now(),
+            m_children: Duration::ZERO,
+        });
+    }
+
+    fn exit(&mut self) {
+        let stack = self
+            .m_frames
+            .iter()
+            .map(|frame| frame.m_function.clone())
+            .collect();
+        let frame = self.m_frames.pop().unwrap();
+        let elapsed = frame.m_start.elapsed();
+        let exclusive = elapsed.saturating_sub(frame.m_children);
+
+        // a recursive call is already counted by the outermost call of the same function
+        let recursive = self
+            .m_frames
+            .iter()
+            .any(|f| f.m_function == frame.m_function);
+
+        let stats = self.m_stats.entry(frame.m_function).or_default();
+        stats.m_calls += 1;
+        stats.m_exclusive += exclusive;
+        if !recursive {
+            stats.m_inclusive += elapsed;
+        }
+
+        *self.m_stacks.entry(stack).or_default() += exclusive;
+
+        if let Some(parent) = self.m_frames.last_mut() {
+            parent.m_children += elapsed;
+        }
+    }
+
+    /// The name each function is reported under. Anonymous functions, and functions sharing a
+    /// name with another one, are told apart by the line they are declared on, and the module
+    /// file when it is not the main script.
+    fn labels(&self) -> HashMap<Function, String> {
+        let shared = self.m_names.values().map(|(name, _)| name).counts();
+        self.m_names
+            .iter()
+            .map(|(function, (name, location))| {
+                let label = match function {
+                    Some(_) if name == "<anonymous>" => format!("<anonymous:{}>", location),
+                    Some(_) if shared[name] > 1 => format!("{}:{}", name, location),
+                    _ => name.clone(),
+                };
+                (function.clone(), label)
+            })
+            .collect()
+    }
+
+    /// Stops timing the top-level script. Returns the stats of every function, sorted by
+    /// exclusive time with the most expensive first.
+    pub fn finish(&mut self) -> Vec<(String, FunctionStats)> {
+        while !self.m_frames.is_empty() {
+            self.exit();
+        }
+
+        let labels = self.labels();
+        self.m_stats
+            .iter()
+            .map(|(function, stats)| (labels[function].clone(), stats.clone()))
+            .sorted_by(|(a_name, a), (b_name, b)| {
+                b.m_exclusive
+                    .cmp(&a.m_exclusive)
+                    .then_with(|| a_name.cmp(b_name))
+            })
+            .collect()
+    }
+
+    /// Renders the exclusive time of each call stack in the collapsed format read by
+    /// `flamegraph.pl` and inferno, one `a;b;c <microseconds>` line per stack.
+    pub fn collapsed_stacks(&self) -> String {
+        let labels = self.labels();
+        self.m_stacks
+            .iter()
+            .map(|(stack, time)| {
+                let stack = stack.iter().map(|function| &labels[function]).join(";");
+                (stack, time)
+            })
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect()
+    }
+}
+
+pub fn format_table(stats: &[(String, FunctionStats)]) -> String {
+    let width = stats
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["function".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!(
+        "{:<width$}  {:>8}  {:>14}  {:>14}\n",
+        "function", "calls", "inclusive (ms)", "exclusive (ms)",
+    );
+    for (name, stats) in stats {
+        table.push_str(&format!(
+            "{:<width$}  {:>8}  {:>14.3}  {:>14.3}\n",
+            name,
+            stats.m_calls,
+            stats.m_inclusive.as_secs_f64() * 1000.0,
+            stats.m_exclusive.as_secs_f64() * 1000.0,
+        ));
+    }
+
+    table
+}
+
+impl ExecutionHook for Profiler {
+    fn on_call(&mut self, callable: &Callable, _arguments: &[Value]) {
+        let module = callable.get_module();
+        let function = callable.span().map(|span| (module.clone(), span));
+        if !self.m_names.contains_key(&function) {
+            let source = self.m_sources.get(module.as_deref());
+            let line = function
+                .as_ref()
+                .map_or(0, |(_, span)| span.line_col(&source).0);
+            let location = match module {
+                Some(module) => format!("{}:{}", file_name(&module), line),
+                None => line.to_string(),
+            };
+            self.m_names
+                .insert(function.clone(), (callable.name(), location));
+        }
+        self.enter(function);
+    }
+
+    fn on_return(&mut self, _callable: &Callable, _result: &Result<Value, Vec<String>>) {
+        self.exit();
+    }
+}
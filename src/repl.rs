@@ -0,0 +1,340 @@
+use crate::ast_v2::Stmt;
+use crate::compat::keywords;
+use crate::environment::Environment;
+use crate::front_end::parse_program;
+use crate::highlight::*;
+use crate::interpreter::*;
+use crate::token_v2::StringLiteral;
+use crate::value::{Callable, Value};
+
+use anyhow::Result;
+use itertools::Itertools;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+
+const PROMPT: &str = "|>  ";
+
+const COMMANDS: [&str; 7] = ["env", "type", "ast", "load", "reset", "time", "help"];
+
+const HELP: &str = "\
+:env [all]    list the globals this session defined, or every global with `all`
+:type <expr>  show the type of a value
+:ast <code>   show the syntax tree of some code
+:load <file>  run a file in this session
+:reset        start a fresh session
+:time <code>  run some code and show how long it took
+:help         show this list
+quit          leave the REPL";
+
+/// Whether `input` stops partway through a statement, inside a string or with a bracket open.
+fn is_unfinished(input: &str) -> bool {
+    let mut depth = 0;
+    let mut start = 0;
+    while let Some(c) = input[start..].chars().next() {
+        let rest = &input[start..];
+        let literal = StringLiteral::scan(rest);
+        let len = match c {
+            _ if literal.is_some() => match literal.and_then(|literal| literal.get_end()) {
+                Some(end) => end,
+                None => return true,
+            },
+            '/' if rest.starts_with("//") => rest.find('\n').unwrap_or(rest.len()),
+            // a name is skipped whole, so an `r` ending it does not start a raw string
+            c if c.is_alphanumeric() || c == '_' => rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len()),
+            '(' | '{' => {
+                depth += 1;
+                1
+            }
+            ')' | '}' => {
+                depth -= 1;
+                1
+            }
+            c => c.len_utf8(),
+        };
+        start += len;
+    }
+
+    depth > 0
+}
+
+/// Ends `input` with the `;` its last statement may leave out, as in `1 + 2`.
+fn complete_statement(input: &str) -> String {
+    let input = format!("{}\n", input);
+    let terminated = format!("{};\n", input.trim_end());
+    match parse_program(&input) {
+        Err(_) if parse_program(&terminated).is_ok() => terminated,
+        _ => input,
+    }
+}
+
+/// Whether the REPL should wait for more lines before running `input`.
+fn is_incomplete(input: &str) -> bool {
+    let trimmed = input.trim();
+    // a blank line runs whatever has been typed, so a mistake is reported rather than waited on
+    if trimmed.is_empty()
+        || input.ends_with('\n')
+        || trimmed.starts_with(':')
+        || matches!(trimmed, "q" | "quit")
+    {
+        return false;
+    }
+
+    parse_program(&complete_statement(input)).is_err()
+        && (is_unfinished(input) || !trimmed.ends_with([';', '}']))
+}
+
+struct ReplHelper {
+    m_env: Rc<RefCell<Environment>>,
+}
+
+impl ReplHelper {
+    /// The fields of the value named by a chain like `a.b`, which only modules have for now.
+    fn fields(&self, path: &str) -> Vec<String> {
+        let mut names = path.split('.');
+        let first = names.next().and_then(|name| self.m_env.borrow().get(name));
+        let value = names.try_fold(first, |value, name| match value {
+            Some(Value::Module(module)) => Ok(module.get(name)),
+            _ => Err(()),
+        });
+
+        match value {
+            Ok(Some(Value::Module(module))) => module.get_names(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn candidates(&self, line: &str, start: usize) -> Vec<String> {
+        let before = &line[..start];
+        if before.trim_start() == ":" {
+            return COMMANDS.iter().map(|command| command.to_string()).collect();
+        }
+
+        match before.strip_suffix('.') {
+            Some(object) => {
+                let path_start = object
+                    .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                    .map_or(0, |i| i + 1);
+                self.fields(&object[path_start..])
+            }
+            None => keywords()
+                .map(String::from)
+                .chain(self.m_env.borrow().get_names())
+                .collect(),
+        }
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        match is_incomplete(ctx.input()) {
+            true => Ok(ValidationResult::Incomplete),
+            false => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        let candidates = self
+            .candidates(line, start)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .sorted()
+            .dedup()
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let env = self.m_env.borrow();
+        Cow::Owned(highlight(line, pos, |name| {
+            matches!(
+                env.get(name),
+                Some(Value::Callable(Callable::NativeFunction(..)))
+            )
+        }))
+    }
+
+    // the bracket to mark moves with the cursor
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Helper for ReplHelper {}
+
+/// An interactive session. Input runs once it forms complete statements, Ctrl-C discards the
+/// input being typed and Ctrl-D or `quit` ends the session.
+pub struct Repl {
+    m_interpreter: Interpreter,
+    m_natives: HashSet<String>,
+    m_editor: Editor<ReplHelper, FileHistory>,
+    m_history: Option<PathBuf>,
+}
+
+fn new_session() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_echo(true);
+    interpreter.set_color(std::io::stdout().is_terminal());
+    interpreter
+}
+
+impl Repl {
+    pub fn new() -> Result<Repl> {
+        let interpreter = new_session();
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(ReplHelper {
+            m_env: interpreter.get_environment(),
+        }));
+
+        let history =
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rlox_history"));
+        if let Some(history) = &history {
+            // there is no history before the first session
+            let _ = editor.load_history(history);
+        }
+
+        let natives = interpreter
+            .get_environment()
+            .borrow()
+            .get_scope()
+            .keys()
+            .cloned()
+            .collect();
+
+        Ok(Repl {
+            m_interpreter: interpreter,
+            m_natives: natives,
+            m_editor: editor,
+            m_history: history,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        println!();
+        loop {
+            let input = match self.m_editor.readline(PROMPT) {
+                Ok(input) => input,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            match input.trim() {
+                "" => continue,
+                "q" | "quit" => break,
+                _ => (),
+            }
+
+            self.m_editor.add_history_entry(input.as_str())?;
+            if let Some(history) = &self.m_history {
+                self.m_editor.save_history(history)?;
+            }
+
+            match input.trim().strip_prefix(':') {
+                Some(command) => self.command(command),
+                // the error has been printed, and the session carries on
+                None => {
+                    let _ = self.m_interpreter.interpret(complete_statement(&input));
+                }
+            }
+            println!();
+        }
+
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str) {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+
+        match (name, argument) {
+            ("env", argument) => self.show_env(argument == "all"),
+            ("type", expr) if !expr.is_empty() => match self.evaluate(expr) {
+                Ok(value) => println!("{}", value.type_name()),
+                Err(err) => println!("{}", err),
+            },
+            ("ast", code) if !code.is_empty() => match parse_program(&complete_statement(code)) {
+                Ok(stmts) => stmts.iter().for_each(|stmt| print!("{}", stmt.tree())),
+                Err(errors) => println!("{}", errors.iter().join("\n")),
+            },
+            ("load", file) if !file.is_empty() => match std::fs::read_to_string(file) {
+                Ok(source) => {
+                    let _ = self.m_interpreter.interpret_file(source, Path::new(file));
+                }
+                Err(err) => println!("Could not read file => {}: {}", file, err),
+            },
+            ("reset", "") => {
+                self.m_interpreter = new_session();
+                if let Some(helper) = self.m_editor.helper_mut() {
+                    helper.m_env = self.m_interpreter.get_environment();
+                }
+                println!("session reset");
+            }
+            ("time", code) if !code.is_empty() => {
+                let start = Instant::now();
+                let _ = self.m_interpreter.interpret(complete_statement(code));
+                println!("time: {:?}", start.elapsed());
+            }
+            ("help", "") => println!("{}", HELP),
+            _ => println!("Unknown command => :{} (see :help)", command),
+        }
+    }
+
+    /// Evaluates a single expression typed after a command.
+    fn evaluate(&mut self, expr: &str) -> Result<Value, String> {
+        match parse_program(&complete_statement(expr)) {
+            Ok(stmts) => match stmts.as_slice() {
+                [Stmt::Expression { m_expression }] => self
+                    .m_interpreter
+                    .evaluate(m_expression)
+                    .map_err(|errors| errors.join("\n")),
+                _ => Err(format!("Expected an expression => {}", expr)),
+            },
+            Err(errors) => Err(errors.iter().join("\n")),
+        }
+    }
+
+    fn show_env(&self, all: bool) {
+        let env = self.m_interpreter.get_environment();
+        let env = env.borrow();
+        env.get_scope()
+            .iter()
+            .filter(|(name, _)| all || !self.m_natives.contains(*name))
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .for_each(|(name, value)| println!("{} = {:?}", name, value));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::ast_v2::*;
+use crate::token_v2::*;
+use crate::visitor::*;
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Function,
+    Parameter,
+    Module,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    m_name: String,
+    m_kind: SymbolKind,
+    m_span: Span,
+    m_range: Span,
+    m_params: Vec<String>,
+    m_container: Option<usize>,
+}
+
+impl Symbol {
+    pub fn get_name(&self) -> &str {
+        &self.m_name
+    }
+
+    pub fn get_kind(&self) -> SymbolKind {
+        self.m_kind
+    }
+
+    /// The span of the name at the declaration site.
+    pub fn get_span(&self) -> Span {
+        self.m_span
+    }
+
+    /// The span of the whole declaration, including an initializer or function body.
+    pub fn get_range(&self) -> Span {
+        self.m_range
+    }
+
+    /// The function this symbol was declared in, if any.
+    pub fn get_container(&self) -> Option<usize> {
+        self.m_container
+    }
+
+    pub fn signature(&self) -> String {
+        match self.m_kind {
+            SymbolKind::Function => format!("fun {}({})", self.m_name, self.m_params.join(", ")),
+            SymbolKind::Variable => format!("let {}", self.m_name),
+            SymbolKind::Parameter => format!("{} (parameter)", self.m_name),
+            SymbolKind::Module => format!("import {}", self.m_name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reference {
+    m_name: String,
+    m_span: Span,
+    m_symbol: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Scope {
+    m_span: Span,
+    m_parent: Option<usize>,
+    m_symbols: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    m_message: String,
+    m_span: Span,
+}
+
+impl ResolveError {
+    pub fn get_span(&self) -> Span {
+        self.m_span
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.m_message)
+    }
+}
+
+/// Static scope analysis over a parsed program. Records every declaration and every use of a
+/// name, linking each use to the declaration it refers to.
+#[derive(Debug)]
+pub struct Resolver {
+    m_symbols: Vec<Symbol>,
+    m_references: Vec<Reference>,
+    m_scopes: Vec<Scope>,
+    m_errors: Vec<ResolveError>,
+    m_current_scope: usize,
+    m_current_function: Option<usize>,
+    m_function_depth: usize,
+}
+
+impl Resolver {
+    pub fn resolve(statements: &[Stmt]) -> Resolver {
+        let mut resolver = Resolver {
+            m_symbols: Vec::new(),
+            m_references: Vec::new(),
+            m_scopes: vec![Scope {
+                m_span: Span::new(0, usize::MAX),
+                m_parent: None,
+                m_symbols: Vec::new(),
+            }],
+            m_errors: Vec::new(),
+            m_current_scope: 0,
+            m_current_function: None,
+            m_function_depth: 0,
+        };
+
+        for stmt in statements {
+            stmt.accept(&mut resolver);
+        }
+
+        // globals are late bound, so a use inside a function may refer to a global that is only
+        // declared further down the file
+        for index in 0..resolver.m_references.len() {
+            if resolver.m_references[index].m_symbol.is_none() {
+                let reference = &resolver.m_references[index];
+                let globals = resolver.m_scopes[0]
+                    .m_symbols
+                    .iter()
+                    .filter(|symbol| resolver.m_symbols[**symbol].m_name == reference.m_name);
+                let symbol = globals
+                    .clone()
+                    .rev()
+                    .find(|symbol| {
+                        resolver.m_symbols[**symbol].m_span.start() < reference.m_span.start()
+                    })
+                    .or_else(|| globals.clone().next())
+                    .copied();
+
+                resolver.m_references[index].m_symbol = symbol;
+            }
+        }
+
+        resolver
+    }
+
+    pub fn get_symbols(&self) -> &[Symbol] {
+        &self.m_symbols
+    }
+
+    pub fn get_errors(&self) -> &[ResolveError] {
+        &self.m_errors
+    }
+
+    /// The symbol declared or referenced at the given byte offset.
+    pub fn symbol_at(&self, offset: usize) -> Option<usize> {
+        self.m_references
+            .iter()
+            .find(|reference| reference.m_span.contains(offset))
+            .and_then(|reference| reference.m_symbol)
+            .or_else(|| {
+                self.m_symbols
+                    .iter()
+                    .position(|symbol| symbol.m_span.contains(offset))
+            })
+    }
+
+    /// The spans of every use of a symbol, not including its declaration.
+    pub fn references_to(&self, symbol: usize) -> Vec<Span> {
+        self.m_references
+            .iter()
+            .filter(|reference| reference.m_symbol == Some(symbol))
+            .map(|reference| reference.m_span)
+            .collect()
+    }
+
+    /// The symbols that can be named at the given byte offset, innermost first.
+    pub fn visible_at(&self, offset: usize) -> Vec<&Symbol> {
+        let mut scope = self
+            .m_scopes
+            .iter()
+            .enumerate()
+            .filter(|(_, scope)| scope.m_span.contains(offset))
+            .min_by_key(|(_, scope)| scope.m_span.end() - scope.m_span.start())
+            .map(|(index, _)| index);
+
+        let mut symbols: Vec<&Symbol> = Vec::new();
+        while let Some(index) = scope {
+            for symbol in self.m_scopes[index].m_symbols.iter().rev() {
+                let symbol = &self.m_symbols[*symbol];
+                let declared = index == 0 || symbol.m_span.start() <= offset;
+                if declared && !symbols.iter().any(|seen| seen.m_name == symbol.m_name) {
+                    symbols.push(symbol);
+                }
+            }
+            scope = self.m_scopes[index].m_parent;
+        }
+
+        symbols
+    }
+
+    fn begin_scope(&mut self, span: Span) {
+        self.m_scopes.push(Scope {
+            m_span: span,
+            m_parent: Some(self.m_current_scope),
+            m_symbols: Vec::new(),
+        });
+        self.m_current_scope = self.m_scopes.len() - 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.m_current_scope = self.m_scopes[self.m_current_scope]
+            .m_parent
+            .expect("cannot end the global scope");
+    }
+
+    fn declare(&mut self, name: &Token, kind: SymbolKind, range: Span, params: &[Token]) -> usize {
+        self.m_symbols.push(Symbol {
+            m_name: format!("{}", name),
+            m_kind: kind,
+            m_span: name.get_span(),
+            m_range: range,
+            m_params: params.iter().map(|param| format!("{}", param)).collect(),
+            m_container: self.m_current_function,
+        });
+
+        let symbol = self.m_symbols.len() - 1;
+        self.m_scopes[self.m_current_scope].m_symbols.push(symbol);
+        symbol
+    }
+
+    fn reference(&mut self, name: &Token) {
+        let name_string = format!("{}", name);
+        let mut scope = Some(self.m_current_scope);
+        let mut symbol = None;
+
+        while let (Some(index), None) = (scope, symbol) {
+            symbol = self.m_scopes[index]
+                .m_symbols
+                .iter()
+                .rev()
+                .find(|symbol| self.m_symbols[**symbol].m_name == name_string)
+                .copied();
+            scope = self.m_scopes[index].m_parent;
+        }
+
+        self.m_references.push(Reference {
+            m_name: name_string,
+            m_span: name.get_span(),
+            m_symbol: symbol,
+        });
+    }
+
+    fn resolve_function(&mut self, params: &[Token], body: &Stmt, span: Span) {
+        self.begin_scope(span);
+        self.m_function_depth += 1;
+
+        for param in params {
+            self.declare(param, SymbolKind::Parameter, param.get_span(), &[]);
+        }
+        body.accept(self);
+
+        self.m_function_depth -= 1;
+        self.end_scope();
+    }
+}
+
+impl ExprVisitor for Resolver {
+    fn visit_binary(&mut self, left: &Expr, _token: &Token, right: &Expr) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_grouping(&mut self, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_literal(&mut self, _token: &Token) {}
+
+    fn visit_unary(&mut self, _token: &Token, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_variable(&mut self, token: &Token) {
+        if let TokenType::Identifier(_) = token.get_token_type() {
+            self.reference(token);
+        }
+    }
+
+    fn visit_assign(&mut self, token: &Token, expression: &Expr) {
+        expression.accept(self);
+        self.reference(token);
+    }
+
+    fn visit_logical(&mut self, left: &Expr, _token: &Token, right: &Expr) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) {
+        callee.accept(self);
+        for argument in arguments {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_function(&mut self, params: &[Token], body: &Stmt) {
+        let span = params
+            .iter()
+            .fold(body.span(), |span, param| span.to(param.get_span()));
+        self.resolve_function(params, body, span);
+    }
+    fn visit_get(&mut self, object: &Expr, _name: &Token) {
+        object.accept(self);
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr], _span: &Span) {
+        for part in parts {
+            part.accept(self);
+        }
+    }
+}
+
+impl StmtVisitor for Resolver {
+    fn visit_block(&mut self, statements: &[Stmt], span: &Span) {
+        self.begin_scope(*span);
+        for stmt in statements {
+            stmt.accept(self);
+        }
+        self.end_scope();
+    }
+
+    fn visit_expression(&mut self, expression: &Expr) {
+        expression.accept(self);
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) {
+        let mut range = name.get_span();
+        if let Some(initializer) = initializer {
+            initializer.accept(self);
+            range = range.to(initializer.span());
+        }
+
+        self.declare(name, SymbolKind::Variable, range, &[]);
+    }
+
+    fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
+        condition.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
+        condition.accept(self);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Stmt) {
+        let range = name.get_span().to(body.span());
+        let symbol = self.declare(name, SymbolKind::Function, range, params);
+
+        let enclosing = self.m_current_function.replace(symbol);
+        self.resolve_function(params, body, range);
+        self.m_current_function = enclosing;
+    }
+
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>) {
+        if self.m_function_depth == 0 {
+            self.m_errors.push(ResolveError {
+                m_message: "Cannot return from top-level code".into(),
+                m_span: keyword.get_span(),
+            });
+        }
+
+        if let Some(value) = value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_import(&mut self, path: &Token, alias: &Option<Token>, names: &[Token]) {
+        let range = path.get_span();
+        if let Some(alias) = alias {
+            self.declare(alias, SymbolKind::Module, range.to(alias.get_span()), &[]);
+        }
+        for name in names {
+            self.declare(name, SymbolKind::Variable, range.to(name.get_span()), &[]);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::environment::Environment;
+use crate::interpreter::{define_native, invalid_argument};
+use crate::value::*;
+
+use itertools::Itertools;
+use std::cell::RefCell;
+use std::rc::Rc;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Strings are indexed by grapheme cluster, so "é" is one character however it is encoded.
+
+/// The longest string, in bytes, that `repeat` builds, so that a huge count is a Lox error rather
+/// than an allocation failure that aborts the process.
+const MAX_REPEAT_LEN: usize = 1 << 30;
+
+fn string_arg<'a>(function: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| invalid_argument(function, "a string", value))
+}
+
+fn list_arg<'a>(function: &str, value: &'a Value) -> Result<&'a [Value], String> {
+    match value {
+        Value::List(list) => Ok(list),
+        value => Err(invalid_argument(function, "a list", value)),
+    }
+}
+
+fn integer_arg(function: &str, value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Number(number) if number.fract() == 0.0 => Ok(*number as i64),
+        value => Err(invalid_argument(function, "an integer", value)),
+    }
+}
+
+/// Resolves an index that may count back from the end, clamped to `0..=len`.
+fn position(index: i64, len: usize) -> usize {
+    match index {
+        index if index < 0 => len.saturating_sub(index.unsigned_abs() as usize),
+        index => (index as usize).min(len),
+    }
+}
+
+fn index_of(string: &str, pattern: &str) -> Option<usize> {
+    string
+        .grapheme_indices(true)
+        .map(|(offset, _)| offset)
+        .chain([string.len()])
+        .position(|offset| string[offset..].starts_with(pattern))
+}
+
+fn to_list(items: impl Iterator<Item = Value>) -> Value {
+    Value::List(Rc::new(items.collect()))
+}
+
+pub fn define_string_natives(env: &Rc<RefCell<Environment>>) {
+    define_native(env, "len", 1, |args| match &args[0] {
+        Value::String(string) => Ok(Value::Number(string.graphemes(true).count() as f64)),
+        Value::List(list) => Ok(Value::Number(list.len() as f64)),
+        value => Err(invalid_argument("len", "a string or a list", value)),
+    });
+
+    define_native(env, "at", 2, |args| {
+        let index = integer_arg("at", &args[1])?;
+        let item = match &args[0] {
+            Value::String(string) => {
+                let graphemes = string.graphemes(true).collect_vec();
+                let len = graphemes.len();
+                (index >= -(len as i64) && index < len as i64)
+                    .then(|| Value::String(graphemes[position(index, len)].into()))
+            }
+            Value::List(list) => (index >= -(list.len() as i64) && index < list.len() as i64)
+                .then(|| list[position(index, list.len())].clone()),
+            value => return Err(invalid_argument("at", "a string or a list", value)),
+        };
+        item.ok_or_else(|| format!("Index out of range => {}", index))
+    });
+
+    define_native(env, "substr", 3, |args| {
+        let string = string_arg("substr", &args[0])?;
+        let graphemes = string.graphemes(true).collect_vec();
+        let start = position(integer_arg("substr", &args[1])?, graphemes.len());
+        let end = position(integer_arg("substr", &args[2])?, graphemes.len());
+        Ok(Value::String(graphemes[start..end.max(start)].concat()))
+    });
+
+    define_native(env, "index_of", 2, |args| {
+        let string = string_arg("index_of", &args[0])?;
+        let pattern = string_arg("index_of", &args[1])?;
+        Ok(index_of(string, pattern).map_or(Value::Nil, |index| Value::Number(index as f64)))
+    });
+
+    define_native(env, "contains", 2, |args| match &args[0] {
+        Value::String(string) => {
+            let pattern = string_arg("contains", &args[1])?;
+            Ok(Value::Boolean(index_of(string, pattern).is_some()))
+        }
+        Value::List(list) => Ok(Value::Boolean(list.iter().any(|v| v.is_equal(&args[1])))),
+        value => Err(invalid_argument("contains", "a string or a list", value)),
+    });
+
+    define_native(env, "starts_with", 2, |args| {
+        let string = string_arg("starts_with", &args[0])?;
+        let prefix = string_arg("starts_with", &args[1])?;
+        Ok(Value::Boolean(string.starts_with(prefix)))
+    });
+
+    define_native(env, "ends_with", 2, |args| {
+        let string = string_arg("ends_with", &args[0])?;
+        let suffix = string_arg("ends_with", &args[1])?;
+        Ok(Value::Boolean(string.ends_with(suffix)))
+    });
+
+    define_native(env, "split", 2, |args| {
+        let string = string_arg("split", &args[0])?;
+        match string_arg("split", &args[1])? {
+            "" => Ok(to_list(
+                string.graphemes(true).map(|g| Value::String(g.into())),
+            )),
+            separator => Ok(to_list(
+                string.split(separator).map(|s| Value::String(s.into())),
+            )),
+        }
+    });
+
+    define_native(env, "join", 2, |args| {
+        let list = list_arg("join", &args[0])?;
+        let separator = string_arg("join", &args[1])?;
+        Ok(Value::String(list.iter().join(separator)))
+    });
+
+    define_native(env, "replace", 3, |args| {
+        let string = string_arg("replace", &args[0])?;
+        let from = match string_arg("replace", &args[1])? {
+            "" => return Err(invalid_argument("replace", "a non-empty pattern", &args[1])),
+            from => from,
+        };
+        let to = string_arg("replace", &args[2])?;
+        Ok(Value::String(string.replace(from, to)))
+    });
+
+    define_native(env, "trim", 1, |args| {
+        Ok(Value::String(string_arg("trim", &args[0])?.trim().into()))
+    });
+
+    define_native(env, "upper", 1, |args| {
+        Ok(Value::String(string_arg("upper", &args[0])?.to_uppercase()))
+    });
+
+    define_native(env, "lower", 1, |args| {
+        Ok(Value::String(string_arg("lower", &args[0])?.to_lowercase()))
+    });
+
+    define_native(env, "chars", 1, |args| {
+        let string = string_arg("chars", &args[0])?;
+        Ok(to_list(
+            string.graphemes(true).map(|g| Value::String(g.into())),
+        ))
+    });
+
+    define_native(env, "repeat", 2, |args| {
+        let string = string_arg("repeat", &args[0])?;
+        match integer_arg("repeat", &args[1])? {
+            count if count < 0 => Err(invalid_argument("repeat", "a non-negative count", &args[1])),
+            count => match string.len().checked_mul(count as usize) {
+                Some(len) if len <= MAX_REPEAT_LEN => {
+                    Ok(Value::String(string.repeat(count as usize)))
+                }
+                _ => Err(format!(
+                    "String too long => repeat would make more than {} bytes",
+                    MAX_REPEAT_LEN
+                )),
+            },
+        }
+    });
+
+    define_native(env, "to_string", 1, |args| {
+        Ok(Value::String(format!("{}", args[0])))
+    });
+
+    define_native(env, "to_number", 1, |args| match &args[0] {
+        Value::String(string) => Ok(string
+            .trim()
+            .parse::<f64>()
+            .map_or(Value::Nil, Value::Number)),
+        Value::Number(number) => Ok(Value::Number(*number)),
+        value => Err(invalid_argument("to_number", "a string", value)),
+    });
+}
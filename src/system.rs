@@ -0,0 +1,57 @@
+use crate::environment::Environment;
+use crate::interpreter::{define_native, invalid_argument};
+use crate::value::*;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+thread_local! {
+    // the command line arguments after the script, shared by the script and its modules
+    static SCRIPT_ARGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn set_script_args(args: Vec<String>) {
+    SCRIPT_ARGS.with(|script_args| *script_args.borrow_mut() = args);
+}
+
+pub fn script_args() -> Value {
+    SCRIPT_ARGS.with(|args| {
+        Value::List(Rc::new(
+            args.borrow().iter().cloned().map(Value::String).collect(),
+        ))
+    })
+}
+
+fn name_arg<'a>(function: &str, value: &'a Value) -> Result<&'a str, String> {
+    match value.as_str() {
+        Some(name) if !name.is_empty() && !name.contains(['=', '\0']) => Ok(name),
+        _ => Err(invalid_argument(function, "a variable name", value)),
+    }
+}
+
+pub fn define_system_natives(env: &Rc<RefCell<Environment>>) {
+    env.borrow_mut().define("args".into(), script_args());
+
+    define_native(env, "getenv", 1, |args| {
+        let name = name_arg("getenv", &args[0])?;
+        Ok(std::env::var(name).map_or(Value::Nil, Value::String))
+    });
+
+    define_native(env, "setenv", 2, |args| {
+        let name = name_arg("setenv", &args[0])?;
+        let value = args[1].to_string();
+        if value.contains('\0') {
+            return Err(invalid_argument("setenv", "a value without NUL", &args[1]));
+        }
+        std::env::set_var(name, value);
+        Ok(Value::Nil)
+    });
+
+    // ends the process at once, so tests registered with `test` do not run
+    define_native(env, "exit", 1, |args| match &args[0] {
+        Value::Number(code) if code.fract() == 0.0 && i32::try_from(*code as i64).is_ok() => {
+            std::process::exit(*code as i32)
+        }
+        value => Err(invalid_argument("exit", "an integer exit code", value)),
+    });
+}
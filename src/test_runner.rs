@@ -0,0 +1,332 @@
+use crate::hook::*;
+use crate::interpreter::*;
+use crate::value::Callable;
+
+use anyhow::Result;
+use itertools::{EitherOrBoth, Itertools};
+use std::cell::RefCell;
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const EXTENSIONS: [&str; 2] = ["lox", "txt"];
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Collects the program output written through `Interpreter::set_output`.
+#[derive(Debug, Default, Clone)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The `// expect: ...` and `// expect runtime error: ...` annotations of a script.
+#[derive(Debug, Default)]
+pub(crate) struct Expectations {
+    m_output: Vec<String>,
+    m_runtime_error: Option<String>,
+}
+
+impl Expectations {
+    pub(crate) fn parse(source: &str) -> Expectations {
+        let mut expectations = Expectations::default();
+
+        for line in source.lines() {
+            if let Some((_, error)) = line.split_once("// expect runtime error:") {
+                expectations.m_runtime_error = Some(error.trim().to_string());
+            } else if let Some((_, output)) = line.split_once("// expect:") {
+                let output = output.strip_prefix(' ').unwrap_or(output);
+                expectations.m_output.push(output.trim_end().to_string());
+            }
+        }
+
+        expectations
+    }
+
+    pub(crate) fn get_output(&self) -> &[String] {
+        &self.m_output
+    }
+
+    pub(crate) fn get_runtime_error(&self) -> Option<&String> {
+        self.m_runtime_error.as_ref()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.m_output.is_empty() && self.m_runtime_error.is_none()
+    }
+}
+
+/// Describes every line where `output` departs from the `expected` lines.
+pub(crate) fn compare_output(expected: &[String], output: &str) -> Vec<String> {
+    expected
+        .iter()
+        .zip_longest(output.lines())
+        .enumerate()
+        .filter_map(|(index, line)| match line {
+            EitherOrBoth::Both(expected, actual) if expected == actual.trim_end() => None,
+            EitherOrBoth::Both(expected, actual) => Some(format!(
+                "line {}: expected {:?}, got {:?}",
+                index + 1,
+                expected,
+                actual.trim_end()
+            )),
+            EitherOrBoth::Left(expected) => Some(format!(
+                "line {}: expected {:?}, got no output",
+                index + 1,
+                expected
+            )),
+            EitherOrBoth::Right(actual) => Some(format!(
+                "line {}: unexpected output {:?}",
+                index + 1,
+                actual.trim_end()
+            )),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum UnitOutcome {
+    Pass,
+    Fail(String),
+    Error(String),
+}
+
+/// The results of the tests a script registered with `test(name, fun)`.
+#[derive(Debug, Default)]
+pub struct UnitTests {
+    m_results: Vec<(String, UnitOutcome)>,
+}
+
+impl UnitTests {
+    /// Runs every test in `tests` as a call of its own, so a test that fails or errors does not
+    /// keep the others from running.
+    pub fn run(tests: Vec<(String, Callable)>, hook: &Hook) -> UnitTests {
+        let results = tests
+            .into_iter()
+            .map(|(name, callable)| {
+                let result = catch_unwind(AssertUnwindSafe(|| callable.call(Vec::new(), hook)));
+                let outcome = match result {
+                    Ok(Ok(_)) => UnitOutcome::Pass,
+                    Ok(Err(errors)) => match errors[0].starts_with(ASSERTION_FAILED) {
+                        true => UnitOutcome::Fail(errors[0].clone()),
+                        false => UnitOutcome::Error(errors.join("; ")),
+                    },
+                    Err(_) => UnitOutcome::Error("the interpreter panicked".into()),
+                };
+                (name, outcome)
+            })
+            .collect();
+
+        UnitTests { m_results: results }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.m_results.is_empty()
+    }
+
+    /// The number of tests that passed, failed an assertion and stopped on another error.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.m_results.iter().fold(
+            (0, 0, 0),
+            |(passed, failed, errors), (_, outcome)| match outcome {
+                UnitOutcome::Pass => (passed + 1, failed, errors),
+                UnitOutcome::Fail(_) => (passed, failed + 1, errors),
+                UnitOutcome::Error(_) => (passed, failed, errors + 1),
+            },
+        )
+    }
+
+    pub fn passed(&self) -> bool {
+        self.counts().0 == self.m_results.len()
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.m_results
+            .iter()
+            .filter_map(|(name, outcome)| match outcome {
+                UnitOutcome::Pass => None,
+                UnitOutcome::Fail(message) => Some(format!("test {:?} failed: {}", name, message)),
+                UnitOutcome::Error(message) => {
+                    Some(format!("test {:?} errored: {}", name, message))
+                }
+            })
+            .collect()
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (name, outcome) in &self.m_results {
+            match outcome {
+                UnitOutcome::Pass => report.push_str(&format!("test {} ... ok\n", name)),
+                UnitOutcome::Fail(message) => {
+                    report.push_str(&format!("test {} ... FAILED\n    {}\n", name, message))
+                }
+                UnitOutcome::Error(message) => {
+                    report.push_str(&format!("test {} ... ERROR\n    {}\n", name, message))
+                }
+            }
+        }
+
+        let (passed, failed, errors) = self.counts();
+        report.push_str(&format!(
+            "\n{} passed, {} failed, {} errors\n",
+            passed, failed, errors
+        ));
+
+        report
+    }
+}
+
+/// Stops a script once it has run for longer than `TIMEOUT`.
+struct Deadline {
+    m_end: Instant,
+}
+
+impl ExecutionHook for Deadline {
+    fn is_stopped(&self) -> bool {
+        Instant::now() >= self.m_end
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Pass,
+    Fail(Vec<String>),
+    Skip,
+}
+
+/// Runs a script in a fresh interpreter, with no input, and checks its output and runtime error
+/// against the script's annotations, then runs the tests it registered. Scripts with neither are
+/// skipped. A script and its tests together get `TIMEOUT` to finish.
+pub fn run_script(path: &Path, source: &str) -> Outcome {
+    let expectations = Expectations::parse(source);
+
+    let capture = Capture::default();
+    let deadline: Rc<RefCell<dyn ExecutionHook>> = Rc::new(RefCell::new(Deadline {
+        m_end: Instant::now() + TIMEOUT,
+    }));
+    let hook: Hook = Some(deadline.clone());
+    let mut interpreter = Interpreter::new();
+    interpreter.set_path(path);
+    interpreter.set_output(Box::new(capture.clone()));
+    interpreter.set_input(Box::new(std::io::empty()));
+    interpreter.set_hook(deadline.clone());
+    let result = catch_unwind(AssertUnwindSafe(|| interpreter.run(source)));
+
+    // drained even when the script failed, so they don't leak into the next script
+    let registered = take_tests();
+    if expectations.is_empty() && registered.is_empty() {
+        return Outcome::Skip;
+    }
+
+    let tests = match result {
+        Ok(Ok(())) => UnitTests::run(registered, &hook),
+        _ => UnitTests::default(),
+    };
+
+    let output = String::from_utf8_lossy(&capture.0.borrow()).to_string();
+    let mut failures = compare_output(&expectations.m_output, &output);
+
+    match (result, &expectations.m_runtime_error) {
+        (Ok(Ok(())), None) => {}
+        (Ok(Ok(())), Some(expected)) => failures.push(format!(
+            "expected runtime error {:?}, but the script finished",
+            expected
+        )),
+        (Ok(Err(RunError::Runtime(errors))), Some(expected))
+            if errors.first() == Some(expected) => {}
+        (Ok(Err(RunError::Runtime(errors))), Some(expected)) => failures.push(format!(
+            "expected runtime error {:?}, got {:?}",
+            expected,
+            errors.join("; ")
+        )),
+        (Ok(Err(RunError::Runtime(errors))), None) => {
+            failures.push(format!("unexpected runtime error {:?}", errors.join("; ")))
+        }
+        (Ok(Err(RunError::Parse(errors))), _) => failures.extend(
+            errors
+                .iter()
+                .map(|err| format!("parse error: {}", err.to_string().trim_end())),
+        ),
+        (Err(_), _) => failures.push("the interpreter panicked".into()),
+    }
+
+    failures.extend(tests.failures());
+    if deadline.borrow().is_stopped() {
+        failures.push(format!("timed out after {:?}", TIMEOUT));
+    }
+
+    match failures.is_empty() {
+        true => Outcome::Pass,
+        false => Outcome::Fail(failures),
+    }
+}
+
+/// Finds every script under `path`, or `path` itself if it is a file, in a stable order.
+pub(crate) fn discover(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut scripts = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') || name == "target" {
+            continue;
+        }
+
+        if path.is_dir() {
+            scripts.extend(discover(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|extension| EXTENSIONS.iter().any(|e| extension == *e))
+        {
+            scripts.push(path);
+        }
+    }
+
+    scripts.sort();
+    Ok(scripts)
+}
+
+/// Runs every script under `path` and prints a report. Returns whether all of them passed.
+pub fn run_tests(path: &Path) -> Result<bool> {
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+
+    for script in discover(path)? {
+        let source = std::fs::read_to_string(&script)?;
+        match run_script(&script, &source) {
+            Outcome::Pass => {
+                passed += 1;
+                println!("PASS {}", script.display());
+            }
+            Outcome::Fail(failures) => {
+                failed += 1;
+                println!("FAIL {}", script.display());
+                failures
+                    .iter()
+                    .flat_map(|failure| failure.lines())
+                    .for_each(|line| println!("    {}", line));
+            }
+            Outcome::Skip => skipped += 1,
+        }
+    }
+
+    println!();
+    println!(
+        "{} passed, {} failed, {} skipped without expectations",
+        passed, failed, skipped
+    );
+
+    Ok(failed == 0)
+}
@@ -1,4 +1,57 @@
-use std::fmt::{Debug, Display};
+use crate::compat::*;
+
+use std::{
+    fmt::{Debug, Display},
+    ops::Deref,
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    m_start: usize,
+    m_end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span {
+            m_start: start,
+            m_end: end,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.m_start
+    }
+
+    pub fn end(&self) -> usize {
+        self.m_end
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.m_start <= offset && offset <= self.m_end
+    }
+
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.m_start.min(other.m_start), self.m_end.max(other.m_end))
+    }
+
+    /// The 1-based line and column of the start of the span within its source.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let start = self.m_start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+
+        (
+            source[..line_start].matches('\n').count() + 1,
+            source[line_start..start].chars().count() + 1,
+        )
+    }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.m_start, self.m_end)
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub enum TokenType {
@@ -32,44 +85,51 @@ pub enum TokenType {
 
     // Keywords.
     And,
-    Class,
+    As,
     Else,
     False,
+    From,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
-    // Prnt,
+    Print,
     Return,
-    Super,
-    This,
     True,
     Var,
     While,
 
-    // Error(String),
-    Skip,
+    Error(String),
+    Unexpected(char),
+    Comment,
     Eof,
 }
 
 impl TokenType {
+    /// The keyword `name` spells in the current dialect, or an identifier.
     pub fn new_identifier(name: &str) -> TokenType {
+        if !is_keyword(name) {
+            return TokenType::Identifier(name.to_string());
+        }
+
         match name {
             "and" => TokenType::And,
-            "class" => TokenType::Class,
+            "as" => TokenType::As,
             "else" => TokenType::Else,
             "false" => TokenType::False,
             "for" => TokenType::For,
+            "from" => TokenType::From,
+            "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "import" => TokenType::Import,
             "nil" => TokenType::Nil,
             "or" => TokenType::Or,
-            // "print" => Token::Print,
+            "print" => TokenType::Print,
             "return" => TokenType::Return,
-            "super" => TokenType::Super,
-            "this" => TokenType::This,
             "true" => TokenType::True,
-            "let" => TokenType::Var,
+            "let" | "var" => TokenType::Var,
             "while" => TokenType::While,
             _ => TokenType::Identifier(name.to_string()),
         }
@@ -82,7 +142,8 @@ impl Display for TokenType {
             f,
             "{}",
             match self {
-                TokenType::Skip => "skip".to_string(),
+                TokenType::Unexpected(c) => c.to_string(),
+                TokenType::Comment => "comment".to_string(),
                 TokenType::Eof => "eof".to_string(),
                 TokenType::LeftParen => "(".to_string(),
                 TokenType::RightParen => ")".to_string(),
@@ -103,26 +164,26 @@ impl Display for TokenType {
                 TokenType::LessEqual => "<=".to_string(),
                 TokenType::Equal => "=".to_string(),
                 TokenType::And => "and".to_string(),
-                TokenType::Class => "class".to_string(),
+                TokenType::As => "as".to_string(),
                 TokenType::Else => "else".to_string(),
                 TokenType::False => "false".to_string(),
+                TokenType::From => "from".to_string(),
                 TokenType::Fun => "fun".to_string(),
                 TokenType::Dot => ".".to_string(),
                 TokenType::For => "for".to_string(),
                 TokenType::If => "if".to_string(),
+                TokenType::Import => "import".to_string(),
                 TokenType::Nil => "nil".to_string(),
                 TokenType::Or => "or".to_string(),
-                // Token::Print => "print".to_string(),
+                TokenType::Print => "print".to_string(),
                 TokenType::Return => "return".to_string(),
-                TokenType::Super => "super".to_string(),
-                TokenType::This => "this".to_string(),
                 TokenType::True => "true".to_string(),
                 TokenType::Var => "let".to_string(),
                 TokenType::While => "while".to_string(),
                 TokenType::Identifier(identifier) => identifier.to_string(),
                 TokenType::String(string) => string.to_string(),
                 TokenType::Number(number) => number.to_string(),
-                // TokenType::Error(error) => error.to_string(),
+                TokenType::Error(error) => error.to_string(),
             }
         )
     }
@@ -136,3 +197,282 @@ impl Debug for TokenType {
         }
     }
 }
+
+#[derive(Clone, PartialEq)]
+pub struct Token {
+    m_token: TokenType,
+    m_span: Span,
+}
+
+impl Token {
+    pub fn new_token(token_type: TokenType, span: Span) -> Token {
+        Token {
+            m_token: token_type,
+            m_span: span,
+        }
+    }
+
+    pub fn get_token_type(&self) -> &TokenType {
+        &self.m_token
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.m_span
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.m_token)
+    }
+}
+
+impl Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.m_token)
+    }
+}
+
+impl Deref for Token {
+    type Target = TokenType;
+
+    fn deref(&self) -> &Self::Target {
+        &self.m_token
+    }
+}
+
+/// A piece of a string literal, with its span relative to the start of the literal: text, or the
+/// source of an expression embedded in it by `${...}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String, Span),
+    Code(Span),
+}
+
+/// A string literal scanned from source: `"..."` with escape sequences and embedded expressions,
+/// `"""..."""` spanning lines, or a raw `r"..."` taken as written, with as many `#` as it needs
+/// around the quotes to hold a `"`, as in `r#"say "hi""#`.
+#[derive(Debug)]
+pub struct StringLiteral {
+    m_value: String,
+    m_parts: Vec<StringPart>,
+    m_body_start: usize,
+    m_end: Option<usize>,
+    m_errors: Vec<(String, Span)>,
+}
+
+impl StringLiteral {
+    /// Scans the string literal at the start of `input`, if one starts there.
+    pub fn scan(input: &str) -> Option<StringLiteral> {
+        if let Some(rest) = input.strip_prefix('r') {
+            let hashes = rest.len() - rest.trim_start_matches('#').len();
+            if !rest[hashes..].starts_with('"') {
+                return None;
+            }
+
+            let body_start = hashes + 2;
+            let closing = format!("\"{}", "#".repeat(hashes));
+            let body = input[body_start..].find(&closing);
+            let value = body.map_or("", |len| &input[body_start..body_start + len]);
+            let span = Span::new(body_start, body_start + value.len());
+            return Some(StringLiteral {
+                m_value: value.into(),
+                m_parts: vec![StringPart::Text(value.into(), span)],
+                m_body_start: body_start,
+                m_end: body.map(|len| body_start + len + closing.len()),
+                m_errors: Vec::new(),
+            });
+        }
+
+        let closing = match input {
+            input if input.starts_with("\"\"\"") => "\"\"\"",
+            input if input.starts_with('"') => "\"",
+            _ => return None,
+        };
+        let mut literal = StringLiteral {
+            m_value: String::new(),
+            m_parts: Vec::new(),
+            m_body_start: closing.len(),
+            m_end: None,
+            m_errors: Vec::new(),
+        };
+
+        let mut index = closing.len();
+        // a line break straight after `"""` only starts the text on the next line
+        if closing.len() == 3 {
+            let rest = &input[index..];
+            index += rest.len() - rest.strip_prefix('\r').unwrap_or(rest).len();
+            index += input[index..].starts_with('\n') as usize;
+        }
+
+        let (mut text, mut text_start) = (String::new(), index);
+        while let Some(c) = input[index..].chars().next() {
+            let rest = &input[index..];
+            if rest.starts_with(closing) {
+                literal.m_end = Some(index + closing.len());
+                break;
+            }
+            if let Some(code) = rest.strip_prefix("${") {
+                let Some(len) = embedded_len(code) else {
+                    break;
+                };
+                literal.end_text(&mut text, Span::new(text_start, index));
+                let code = Span::new(index + 2, index + 2 + len);
+                literal.m_parts.push(StringPart::Code(code));
+                index = code.end() + 1;
+                text_start = index;
+                continue;
+            }
+            if c != '\\' {
+                text.push(c);
+                index += c.len_utf8();
+                continue;
+            }
+
+            let Some((escaped, len)) = unescape(rest) else {
+                break;
+            };
+            match escaped {
+                Ok(c) => text.push(c),
+                Err(message) => {
+                    text.push_str(&rest[..len]);
+                    literal
+                        .m_errors
+                        .push((message, Span::new(index, index + len)));
+                }
+            }
+            index += len;
+        }
+        literal.end_text(&mut text, Span::new(text_start, index));
+
+        literal.m_value = literal
+            .m_parts
+            .iter()
+            .map(|part| match part {
+                StringPart::Text(text, _) => text,
+                StringPart::Code(span) => &input[span.start() - 2..span.end() + 1],
+            })
+            .collect();
+        Some(literal)
+    }
+
+    /// Ends the text part taken so far, if there is one.
+    fn end_text(&mut self, text: &mut String, span: Span) {
+        if !text.is_empty() {
+            self.m_parts
+                .push(StringPart::Text(std::mem::take(text), span));
+        }
+    }
+
+    /// The text of the literal, with any embedded expression left as written.
+    pub fn get_value(&self) -> &str {
+        &self.m_value
+    }
+
+    /// The text and the embedded expressions that make up the literal, in order.
+    pub fn get_parts(&self) -> &[StringPart] {
+        &self.m_parts
+    }
+
+    /// Whether the literal embeds an expression, so that its value is only known when it runs.
+    pub fn is_interpolated(&self) -> bool {
+        self.m_parts
+            .iter()
+            .any(|part| matches!(part, StringPart::Code(_)))
+    }
+
+    /// Where the text starts, after the opening delimiter.
+    pub fn get_body_start(&self) -> usize {
+        self.m_body_start
+    }
+
+    /// Where the literal ends, after the closing delimiter, or `None` when it is unterminated.
+    pub fn get_end(&self) -> Option<usize> {
+        self.m_end
+    }
+
+    /// The invalid escape sequences, with spans relative to the start of the literal.
+    pub fn get_errors(&self) -> &[(String, Span)] {
+        &self.m_errors
+    }
+}
+
+/// The length of the expression embedded at the start of `input`, up to the `}` closing it, or
+/// `None` when the input ends first. Braces and strings inside it are skipped over whole.
+fn embedded_len(input: &str) -> Option<usize> {
+    let (mut index, mut depth) = (0, 0);
+    while let Some(c) = input[index..].chars().next() {
+        if let Some(literal) = StringLiteral::scan(&input[index..]) {
+            index += literal.get_end()?;
+            continue;
+        }
+
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            // a name is skipped whole, so an `r` ending it does not start a raw string
+            c if c.is_alphanumeric() || c == '_' => {
+                let rest = &input[index..];
+                index += rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                continue;
+            }
+            _ => (),
+        }
+        index += c.len_utf8();
+    }
+
+    None
+}
+
+/// The character the escape sequence at the start of `input` stands for, or an error, with the
+/// length of the sequence. `None` when the input ends after the backslash.
+fn unescape(input: &str) -> Option<(Result<char, String>, usize)> {
+    let escaped = input[1..].chars().next()?;
+    let len = 1 + escaped.len_utf8();
+
+    let c = match escaped {
+        '"' => '"',
+        '\\' => '\\',
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '$' => '$',
+        'u' => return Some(unescape_unicode(input)),
+        c => {
+            let message = format!("Invalid escape sequence '\\{}'", c.escape_debug());
+            return Some((Err(message), len));
+        }
+    };
+
+    Some((Ok(c), len))
+}
+
+/// A `\u{...}` escape of one to six hex digits naming a Unicode scalar value.
+fn unescape_unicode(input: &str) -> (Result<char, String>, usize) {
+    let digits = input[2..]
+        .strip_prefix('{')
+        .and_then(|rest| rest.find('}').map(|end| &rest[..end]))
+        .filter(|digits| digits.len() <= 8 && !digits.contains(['"', '\n']));
+
+    let Some(digits) = digits else {
+        return (Err("Invalid unicode escape '\\u'".into()), 2);
+    };
+
+    let len = digits.len() + 4;
+    let c = Some(digits)
+        .filter(|digits| (1..=6).contains(&digits.len()))
+        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .and_then(char::from_u32);
+    match c {
+        Some(c) => (Ok(c), len),
+        None => (
+            Err(format!("Invalid unicode escape '{}'", &input[..len])),
+            len,
+        ),
+    }
+}
@@ -0,0 +1,87 @@
+use crate::ast_v2::Stmt;
+use crate::environment::Environment;
+use crate::hook::*;
+use crate::module::file_name;
+use crate::value::*;
+
+use itertools::Itertools;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Prints an indented log of execution to stderr, nesting the statements of each call under
+/// the call that ran them.
+#[derive(Debug)]
+pub struct Tracer {
+    m_sources: Sources,
+    m_depth: usize,
+}
+
+impl Tracer {
+    pub fn new(source: String) -> Tracer {
+        Tracer {
+            m_sources: Sources::new(source),
+            m_depth: 0,
+        }
+    }
+
+    fn log(&self, indent: usize, message: String) {
+        eprintln!("{}{}", " ".repeat(self.m_depth * 4 + indent), message);
+    }
+}
+
+/// Functions are shown by name rather than by their whole body.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Callable(callable) => format!("<fun {}>", callable.name()),
+        value => format!("{:?}", value),
+    }
+}
+
+impl ExecutionHook for Tracer {
+    fn on_statement(&mut self, stmt: &Stmt, env: &Rc<RefCell<Environment>>) {
+        let module = env.borrow().get_module();
+        let source = self.m_sources.get(module.as_deref());
+        if matches!(stmt, Stmt::Block { .. }) || stmt.is_comment(&source) {
+            return;
+        }
+
+        let (line, _) = stmt.span().line_col(&source);
+        let text = source.lines().nth(line - 1).unwrap_or_default().trim();
+        match module {
+            Some(module) => self.log(
+                0,
+                format!("[{} line {}] {}", file_name(&module), line, text),
+            ),
+            None => self.log(0, format!("[line {}] {}", line, text)),
+        }
+    }
+
+    fn on_call(&mut self, callable: &Callable, arguments: &[Value]) {
+        let arguments = arguments.iter().map(describe).join(", ");
+        self.log(2, format!("call {}({})", callable.name(), arguments));
+        self.m_depth += 1;
+    }
+
+    fn on_return(&mut self, callable: &Callable, result: &Result<Value, Vec<String>>) {
+        self.m_depth -= 1;
+        match result {
+            Ok(value) => self.log(
+                2,
+                format!("return {} => {}", callable.name(), describe(value)),
+            ),
+            Err(_) => self.log(2, format!("unwind {}", callable.name())),
+        }
+    }
+
+    fn on_define(&mut self, name: &str, value: &Value) {
+        self.log(2, format!("define {} = {}", name, describe(value)));
+    }
+
+    fn on_assign(&mut self, name: &str, value: &Value) {
+        self.log(2, format!("assign {} = {}", name, describe(value)));
+    }
+
+    fn on_error(&mut self, message: &str) {
+        self.log(2, format!("error: {}", message));
+    }
+}
@@ -1,5 +1,7 @@
 use crate::environment::Environment;
-use crate::token_v2::TokenType;
+use crate::hook::Hook;
+use crate::module::Module;
+use crate::token_v2::{Span, Token};
 use crate::visitor::ErrorValue;
 use crate::{ast_v2::*, visitor::StmtEvaluator};
 
@@ -7,33 +9,42 @@ use anyhow::Result;
 use itertools::Itertools;
 use std::cell::RefCell;
 use std::fmt::{Debug, Display};
+use std::path::PathBuf;
 use std::rc::Rc;
 
-type Token = TokenType;
+/// A function implemented in Rust. An `Err` is raised as a runtime error at the call site.
+pub type NativeFn = fn(Vec<Value>) -> Result<Value, String>;
 
 #[derive(Clone)]
 pub enum Callable {
-    NativeFunction(
-        Option<Rc<RefCell<Environment>>>,
-        usize,
-        Box<fn(Vec<Value>) -> Value>,
-    ),
+    NativeFunction(Option<Rc<RefCell<Environment>>>, usize, Box<NativeFn>),
     Function(
         Option<Rc<RefCell<Environment>>>,
         Vec<Token>,
         usize,
         Box<Stmt>,
+        Option<Token>,
     ),
 }
 
 impl Callable {
-    pub fn call(&self, arguments: Vec<(Option<String>, Value)>) -> Result<Value, Vec<String>> {
+    pub fn call(
+        &self,
+        arguments: Vec<(Option<String>, Value)>,
+        hook: &Hook,
+    ) -> Result<Value, Vec<String>> {
         match self {
             Callable::NativeFunction(_env, _arity, call) => {
-                Ok(call(arguments.into_iter().map(|(_, v)| v).collect()))
+                call(arguments.into_iter().map(|(_, v)| v).collect()).map_err(|err| {
+                    if let Some(hook) = hook {
+                        hook.borrow_mut().on_error(&err);
+                    }
+                    vec![err]
+                })
             }
-            Callable::Function(env, params, _arity, stmt) => {
-                let inner_scope = Environment::new_scope(env.as_ref().unwrap());
+            Callable::Function(env, params, _arity, stmt, _name) => {
+                let env = env.as_ref().unwrap();
+                let inner_scope = Environment::new_scope(env);
 
                 for (param, (_ident, argument)) in params.iter().zip(arguments.iter()) {
                     inner_scope
@@ -42,10 +53,15 @@ impl Callable {
                 }
                 // dbg!(&inner_scope);
 
-                let mut visitor = StmtEvaluator::new(&inner_scope);
-                stmt.accept(&mut visitor);
+                if let Some(hook) = hook {
+                    let arguments = arguments.iter().map(|(_, v)| v.clone()).collect_vec();
+                    hook.borrow_mut().on_call(self, &arguments);
+                }
+
+                let mut visitor = StmtEvaluator::new(&inner_scope, hook);
+                visitor.execute(stmt);
 
-                match visitor.get_result() {
+                let result = match visitor.get_result() {
                     Ok(()) => Ok(Value::Nil),
                     Err(value) => match value.last() {
                         Some(ErrorValue::Return(value)) => Ok(value.clone()),
@@ -57,7 +73,32 @@ impl Callable {
                             })
                             .collect()),
                     },
+                };
+
+                if let Some(hook) = hook {
+                    hook.borrow_mut().on_return(self, &result);
                 }
+
+                result
+            }
+        }
+    }
+
+    /// The span of a Lox function's body.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Callable::NativeFunction(..) => None,
+            Callable::Function(_env, _params, _arity, stmt, _name) => Some(stmt.span()),
+        }
+    }
+
+    /// The file of the module a Lox function was declared in, or `None` for the main script and
+    /// native functions.
+    pub fn get_module(&self) -> Option<PathBuf> {
+        match self {
+            Callable::NativeFunction(..) => None,
+            Callable::Function(env, _params, _arity, _stmt, _name) => {
+                env.as_ref().and_then(|env| env.borrow().get_module())
             }
         }
     }
@@ -65,7 +106,16 @@ impl Callable {
     pub fn arity(&self) -> usize {
         match self {
             Callable::NativeFunction(_env, arity, _call) => *arity,
-            Callable::Function(_ident, _env, arity, _stmt) => *arity,
+            Callable::Function(_ident, _env, arity, _stmt, _name) => *arity,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Callable::NativeFunction(_env, _arity, _call) => "<native function>".into(),
+            Callable::Function(_env, _params, _arity, _stmt, name) => name
+                .as_ref()
+                .map_or("<anonymous>".into(), |name| format!("{}", name)),
         }
     }
 }
@@ -74,7 +124,7 @@ impl Debug for Callable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Callable::NativeFunction(_env, _arity, _call) => write!(f, "<native function>"),
-            Callable::Function(_env, _param, _arity, _stmt) => {
+            Callable::Function(_env, _param, _arity, _stmt, _name) => {
                 write!(f, "fun ({}) {:?}", _param.iter().join(", "), _stmt)
             }
         }
@@ -87,6 +137,8 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Callable(Callable),
+    Module(Rc<Module>),
+    List(Rc<Vec<Value>>),
     Nil,
 }
 
@@ -108,6 +160,16 @@ impl Value {
             (Value::String(string), Value::String(other_string)) => string == other_string,
             (Value::Boolean(boolean), Value::Boolean(other_boolean)) => boolean == other_boolean,
             (Value::Callable(_callable), Value::Callable(_other_callable)) => false,
+            (Value::Module(module), Value::Module(other_module)) => {
+                Rc::ptr_eq(module, other_module)
+            }
+            (Value::List(list), Value::List(other_list)) => {
+                list.len() == other_list.len()
+                    && list
+                        .iter()
+                        .zip(other_list.iter())
+                        .all(|(a, b)| a.is_equal(b))
+            }
             (Value::Nil, _) => false,
             (_, Value::Nil) => false,
             _ => false,
@@ -146,6 +208,19 @@ impl Value {
     //     }
     // }
 
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Callable(Callable::NativeFunction(..)) => "native function",
+            Value::Callable(Callable::Function(..)) => "function",
+            Value::Module(_) => "module",
+            Value::List(_) => "list",
+            Value::Nil => "nil",
+        }
+    }
+
     pub fn as_number(&self) -> Option<f64> {
         match self {
             Value::Number(number) => Some(*number),
@@ -153,6 +228,13 @@ impl Value {
         }
     }
 
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
     // pub fn as_string(&self) -> Option<String> {
     //     match self {
     //         Value::String(string) => Some(string.clone()),
@@ -182,6 +264,12 @@ impl std::fmt::Debug for Value {
             Value::String(string) => write!(f, "\"{}\"", string),
             Value::Boolean(boolean) => write!(f, "{}", boolean),
             Value::Callable(callable) => write!(f, "{:?}", callable),
+            Value::Module(module) => write!(f, "<module {}>", module.get_name()),
+            Value::List(list) => write!(
+                f,
+                "[{}]",
+                list.iter().map(|v| format!("{:?}", v)).join(", ")
+            ),
             Value::Nil => write!(f, "nil"),
         }
     }
@@ -3,34 +3,43 @@ use std::fmt::Display;
 use std::rc::Rc;
 
 use crate::ast_v2::*;
+use crate::compat::*;
 use crate::environment::Environment;
+use crate::hook::*;
+use crate::module::*;
 use crate::token_v2::*;
 use crate::value::*;
 
 pub trait ExprVisitor {
-    fn visit_binary(&mut self, left: &Expr, token: &TokenType, right: &Expr);
+    fn visit_binary(&mut self, left: &Expr, token: &Token, right: &Expr);
     fn visit_grouping(&mut self, expression: &Expr);
-    fn visit_literal(&mut self, token: &TokenType);
-    fn visit_unary(&mut self, token: &TokenType, expression: &Expr);
-    fn visit_variable(&mut self, token: &TokenType);
-    fn visit_assign(&mut self, token: &TokenType, expression: &Expr);
-    fn visit_logical(&mut self, left: &Expr, token: &TokenType, right: &Expr);
+    fn visit_literal(&mut self, token: &Token);
+    fn visit_unary(&mut self, token: &Token, expression: &Expr);
+    fn visit_variable(&mut self, token: &Token);
+    fn visit_assign(&mut self, token: &Token, expression: &Expr);
+    fn visit_logical(&mut self, left: &Expr, token: &Token, right: &Expr);
     fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]);
-    fn visit_function(&mut self, params: &[TokenType], body: &Stmt);
+    fn visit_function(&mut self, params: &[Token], body: &Stmt);
+    fn visit_get(&mut self, object: &Expr, name: &Token);
+    fn visit_interpolation(&mut self, parts: &[Expr], span: &Span);
 }
 
 pub struct ExprEvaluator {
     m_env: Rc<RefCell<Environment>>,
+    m_hook: Hook,
     m_result: Vec<Value>,
     m_errors: Vec<String>,
+    m_error_span: Option<Span>,
 }
 
 impl ExprEvaluator {
-    pub fn new(env: &Rc<RefCell<Environment>>) -> Self {
+    pub fn new(env: &Rc<RefCell<Environment>>, hook: &Hook) -> Self {
         Self {
             m_env: env.clone(),
+            m_hook: hook.clone(),
             m_result: Vec::new(),
             m_errors: Vec::new(),
+            m_error_span: None,
         }
     }
 
@@ -44,10 +53,32 @@ impl ExprEvaluator {
             Err(self.m_errors.clone())
         }
     }
+
+    /// The span of the expression the first error came from. An error raised inside a called
+    /// function is put on the call.
+    pub fn get_error_span(&self) -> Option<Span> {
+        self.m_error_span
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_error(&message);
+        }
+
+        self.m_error_span.get_or_insert(span);
+        self.m_errors.push(message);
+    }
+
+    fn branch(&self, span: Span, taken: bool) {
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_branch(span, taken, &self.m_env);
+        }
+    }
 }
 
 impl ExprVisitor for ExprEvaluator {
-    fn visit_binary(&mut self, left: &Expr, token: &TokenType, right: &Expr) {
+    fn visit_binary(&mut self, left: &Expr, token: &Token, right: &Expr) {
+        let span = left.span().to(right.span());
         left.accept(self);
         right.accept(self);
 
@@ -57,7 +88,7 @@ impl ExprVisitor for ExprEvaluator {
 
         match (self.m_result.pop(), self.m_result.pop()) {
             (Some(Value::Number(right)), Some(Value::Number(left))) => {
-                self.m_result.push(match token {
+                let value = match token.get_token_type() {
                     TokenType::Minus => Value::Number(left - right),
                     TokenType::Plus => Value::Number(left + right),
                     TokenType::Slash => Value::Number(left / right),
@@ -69,14 +100,14 @@ impl ExprVisitor for ExprEvaluator {
                     TokenType::BangEqual => Value::Boolean(left != right),
                     TokenType::EqualEqual => Value::Boolean(left == right),
                     token_type => {
-                        self.m_errors
-                            .push(format!("Invalid binary operator => {}", token_type));
+                        self.error(span, format!("Invalid binary operator => {}", token_type));
                         Value::Nil
                     }
-                });
+                };
+                self.m_result.push(value);
             }
             (Some(Value::String(right)), Some(Value::String(left))) => {
-                self.m_result.push(match token {
+                let value = match token.get_token_type() {
                     TokenType::Plus => Value::String(format!("{}{}", left, right)),
                     TokenType::Greater => Value::Boolean(left > right),
                     TokenType::GreaterEqual => Value::Boolean(left >= right),
@@ -85,27 +116,30 @@ impl ExprVisitor for ExprEvaluator {
                     TokenType::BangEqual => Value::Boolean(left != right),
                     TokenType::EqualEqual => Value::Boolean(left == right),
                     token_type => {
-                        self.m_errors
-                            .push(format!("Invalid binary operator => {}", token_type));
+                        self.error(span, format!("Invalid binary operator => {}", token_type));
                         Value::Nil
                     }
-                });
+                };
+                self.m_result.push(value);
             }
             (Some(right), Some(left)) => {
-                self.m_result.push(match token {
+                let value = match token.get_token_type() {
                     TokenType::BangEqual => Value::Boolean(!left.is_equal(&right)),
                     TokenType::EqualEqual => Value::Boolean(left.is_equal(&right)),
                     token_type => {
-                        self.m_errors
-                            .push(format!("Invalid binary operator => {}", token_type));
+                        self.error(span, format!("Invalid binary operator => {}", token_type));
                         Value::Nil
                     }
-                });
+                };
+                self.m_result.push(value);
             }
-            (right, left) => self.m_errors.push(format!(
-                "Invalid binary expression => {:?} {:?} {:?}",
-                left, token, right
-            )),
+            (right, left) => self.error(
+                span,
+                format!(
+                    "Invalid binary expression => {:?} {:?} {:?}",
+                    left, token, right
+                ),
+            ),
         }
     }
 
@@ -113,91 +147,104 @@ impl ExprVisitor for ExprEvaluator {
         expression.accept(self);
     }
 
-    fn visit_literal(&mut self, token: &TokenType) {
-        self.m_result.push(match token {
+    fn visit_literal(&mut self, token: &Token) {
+        let span = token.get_span();
+        let value = match token.get_token_type() {
             TokenType::Number(number) => Value::Number(*number),
             TokenType::String(string) => Value::String(string.clone()),
             TokenType::True => Value::Boolean(true),
             TokenType::False => Value::Boolean(false),
             TokenType::Nil => Value::Nil,
             token => {
-                self.m_errors
-                    .push(format!("Invalid literal expression => {:?}", token));
+                self.error(span, format!("Invalid literal expression => {:?}", token));
                 Value::Nil
             }
-        });
+        };
+        self.m_result.push(value);
     }
 
-    fn visit_unary(&mut self, token: &TokenType, expression: &Expr) {
+    fn visit_unary(&mut self, token: &Token, expression: &Expr) {
+        let span = token.get_span().to(expression.span());
         expression.accept(self);
 
         if !self.m_errors.is_empty() {
             return;
         }
 
+        if dialect() == Dialect::Clox && token.get_token_type() == &TokenType::Bang {
+            if let Some(value) = self.m_result.pop() {
+                self.m_result.push(Value::Boolean(!value.is_truthy()));
+                return;
+            }
+        }
+
         match self.m_result.pop() {
             Some(Value::Number(number)) => {
-                self.m_result.push(match token {
+                let value = match token.get_token_type() {
                     TokenType::Minus => Value::Number(-number),
                     TokenType::Bang => {
                         Value::Boolean(!Value::Number(number).is_equal(&Value::Number(0.0)))
                     }
                     token_type => {
-                        self.m_errors
-                            .push(format!("Invalid unary operator => {}", token_type));
+                        self.error(span, format!("Invalid unary operator => {}", token_type));
                         Value::Nil
                     }
-                });
+                };
+                self.m_result.push(value);
             }
             Some(Value::Boolean(boolean)) => {
-                self.m_result.push(match token {
+                let value = match token.get_token_type() {
                     TokenType::Bang => Value::Boolean(!boolean),
                     token_type => {
-                        self.m_errors
-                            .push(format!("Invalid unary operator => {}", token_type));
+                        self.error(span, format!("Invalid unary operator => {}", token_type));
                         Value::Nil
                     }
-                });
+                };
+                self.m_result.push(value);
             }
             Some(value) => {
-                self.m_errors.push(format!(
-                    "Invalid unary expression => {:?} {:?}",
-                    token, value
-                ));
+                self.error(
+                    span,
+                    format!("Invalid unary expression => {:?} {:?}", token, value),
+                );
             }
             None => {
-                self.m_errors.push(format!(
-                    "Invalid unary expression => {:?} {:?}",
-                    token, self.m_result
-                ));
+                self.error(
+                    span,
+                    format!(
+                        "Invalid unary expression => {:?} {:?}",
+                        token, self.m_result
+                    ),
+                );
             }
         }
     }
 
-    fn visit_variable(&mut self, token: &TokenType) {
-        self.m_result.push(match token {
+    fn visit_variable(&mut self, token: &Token) {
+        let span = token.get_span();
+        let value = match token.get_token_type() {
             TokenType::Identifier(identifier) => match identifier.as_str() {
                 "true" => Value::Boolean(true),
                 "false" => Value::Boolean(false),
                 "nil" => Value::Nil,
-                identifier => match self.m_env.borrow().get(identifier) {
-                    Some(value) => value.clone(),
-                    None => {
-                        self.m_errors
-                            .push(format!("Undefined variable => {:?}", token));
+                identifier => {
+                    let value = self.m_env.borrow().get(identifier);
+                    value.unwrap_or_else(|| {
+                        self.error(span, format!("Undefined variable => {:?}", token));
                         Value::Nil
-                    }
-                },
+                    })
+                }
             },
             token => {
-                self.m_errors
-                    .push(format!("Invalid variable expression => {:?}", token));
+                self.error(span, format!("Invalid variable expression => {:?}", token));
                 Value::Nil
             }
-        });
+        };
+        self.m_result.push(value);
     }
 
-    fn visit_assign(&mut self, token: &TokenType, expression: &Expr) {
+    fn visit_assign(&mut self, token: &Token, expression: &Expr) {
+        let span = token.get_span().to(expression.span());
         expression.accept(self);
 
         if !self.m_errors.is_empty() {
@@ -205,61 +252,91 @@ impl ExprVisitor for ExprEvaluator {
         }
 
         match self.m_result.pop() {
-            Some(value) => match token {
+            Some(value) => match token.get_token_type() {
                 TokenType::Identifier(identifier) => {
-                    if let Err(err) = self
+                    let assigned = self
                         .m_env
                         .borrow_mut()
-                        .assign(identifier.to_string(), value.clone())
-                    {
-                        self.m_errors.push(format!("{}", err));
+                        .assign(identifier.to_string(), value.clone());
+                    match assigned {
+                        Ok(()) => {
+                            if let Some(hook) = &self.m_hook {
+                                hook.borrow_mut().on_assign(identifier, &value);
+                            }
+                        }
+                        Err(err) => self.error(span, format!("{}", err)),
                     }
 
                     self.m_result.push(value);
                 }
                 token => {
-                    self.m_errors
-                        .push(format!("Invalid assign expression => {:?}", token));
+                    self.error(span, format!("Invalid assign expression => {:?}", token));
                 }
             },
             None => {
-                self.m_errors.push(format!(
-                    "Invalid assign expression => {:?} {:?}",
-                    token, self.m_result
-                ));
+                self.error(
+                    span,
+                    format!(
+                        "Invalid assign expression => {:?} {:?}",
+                        token, self.m_result
+                    ),
+                );
             }
         }
     }
 
-    fn visit_logical(&mut self, left: &Expr, token: &TokenType, right: &Expr) {
+    fn visit_logical(&mut self, left: &Expr, token: &Token, right: &Expr) {
+        let span = left.span().to(right.span());
         left.accept(self);
 
         if !self.m_errors.is_empty() {
             return;
         }
 
+        // clox yields whichever operand decided the result, of any type
+        if dialect() == Dialect::Clox {
+            if let Some(left) = self.m_result.last() {
+                let short_circuit = match token.get_token_type() {
+                    TokenType::Or => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                self.branch(token.get_span(), !short_circuit);
+                if !short_circuit {
+                    self.m_result.pop();
+                    right.accept(self);
+                }
+                return;
+            }
+        }
+
         match self.m_result.pop() {
             Some(Value::Boolean(left)) => {
-                if token == &TokenType::Or && left {
+                if token.get_token_type() == &TokenType::Or && left {
+                    self.branch(token.get_span(), false);
                     self.m_result.push(Value::Boolean(true));
                     return;
-                } else if token == &TokenType::And && !left {
+                } else if token.get_token_type() == &TokenType::And && !left {
+                    self.branch(token.get_span(), false);
                     self.m_result.push(Value::Boolean(false));
                     return;
                 }
+                self.branch(token.get_span(), true);
             }
             Some(left) => {
-                self.m_errors.push(format!(
-                    "Invalid logical expression => {:?} {:?}",
-                    token, left
-                ));
+                self.error(
+                    span,
+                    format!("Invalid logical expression => {:?} {:?}", token, left),
+                );
                 return;
             }
             None => {
-                self.m_errors.push(format!(
-                    "Invalid logical expression => {:?} {:?}",
-                    token, self.m_result
-                ));
+                self.error(
+                    span,
+                    format!(
+                        "Invalid logical expression => {:?} {:?}",
+                        token, self.m_result
+                    ),
+                );
                 return;
             }
         }
@@ -272,26 +349,31 @@ impl ExprVisitor for ExprEvaluator {
 
         match self.m_result.pop() {
             Some(Value::Boolean(right)) => {
-                if matches!(token, TokenType::Or | TokenType::And) {
+                if matches!(token.get_token_type(), TokenType::Or | TokenType::And) {
                     self.m_result.push(Value::Boolean(right));
                 }
             }
             Some(right) => {
-                self.m_errors.push(format!(
-                    "Invalid logical expression => {:?} {:?}",
-                    token, right
-                ));
+                self.error(
+                    span,
+                    format!("Invalid logical expression => {:?} {:?}", token, right),
+                );
             }
             None => {
-                self.m_errors.push(format!(
-                    "Invalid logical expression => {:?} {:?}",
-                    token, self.m_result
-                ));
+                self.error(
+                    span,
+                    format!(
+                        "Invalid logical expression => {:?} {:?}",
+                        token, self.m_result
+                    ),
+                );
             }
         }
     }
 
     fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) {
+        // errors from the call, including those raised inside the function, point at the callee
+        let span = callee.span();
         callee.accept(self);
 
         if !self.m_errors.is_empty() {
@@ -301,8 +383,7 @@ impl ExprVisitor for ExprEvaluator {
         let callee = match self.m_result.pop() {
             Some(callee) => callee,
             None => {
-                self.m_errors
-                    .push(format!("Invalid call expression => {:?}", callee));
+                self.error(span, format!("Invalid call expression => {:?}", callee));
                 return;
             }
         };
@@ -331,10 +412,10 @@ impl ExprVisitor for ExprEvaluator {
         match callee {
             Value::Callable(callable) => {
                 if callable.arity() != arguments.len() {
-                    self.m_errors.push(format!(
-                        "Invalid call expression => {:?}{:?}",
-                        callable, arguments
-                    ));
+                    self.error(
+                        span,
+                        format!("Invalid call expression => {:?}{:?}", callable, arguments),
+                    );
                     return;
                 }
 
@@ -344,38 +425,86 @@ impl ExprVisitor for ExprEvaluator {
                     .map(|(value, _ident)| (None, value))
                     .collect();
 
-                match callable.call(arguments) {
+                match callable.call(arguments, &self.m_hook) {
                     Ok(result) => self.m_result.push(result),
-                    Err(err) => self.m_errors.extend(err),
+                    Err(err) => {
+                        self.m_error_span.get_or_insert(span);
+                        self.m_errors.extend(err)
+                    }
                 }
             }
             callee => {
-                self.m_errors
-                    .push(format!("Invalid call expression => {:?}", callee));
+                self.error(span, format!("Invalid call expression => {:?}", callee));
             }
         }
     }
 
-    fn visit_function(&mut self, params: &[TokenType], body: &Stmt) {
+    fn visit_function(&mut self, params: &[Token], body: &Stmt) {
         let callable = Value::Callable(Callable::Function(
             Some(self.m_env.clone()),
             params.to_vec(),
             params.len(),
             Box::new(body.clone()),
+            None,
         ));
 
         self.m_result.push(callable);
     }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) {
+        let span = object.span().to(name.get_span());
+        object.accept(self);
+
+        if !self.m_errors.is_empty() {
+            return;
+        }
+
+        match self.m_result.pop() {
+            Some(Value::Module(module)) => match module.get(&format!("{}", name)) {
+                Some(value) => self.m_result.push(value),
+                None => self.error(
+                    span,
+                    format!("Undefined export => {}.{}", module.get_name(), name),
+                ),
+            },
+            object => self.error(
+                span,
+                format!(
+                    "Invalid property access => {:?}.{}",
+                    object.unwrap_or(Value::Nil),
+                    name
+                ),
+            ),
+        }
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expr], _span: &Span) {
+        let mut string = String::new();
+        for part in parts {
+            part.accept(self);
+
+            if !self.m_errors.is_empty() {
+                return;
+            }
+
+            if let Some(value) = self.m_result.pop() {
+                string.push_str(&value.to_string());
+            }
+        }
+
+        self.m_result.push(Value::String(string));
+    }
 }
 
 pub trait StmtVisitor {
-    fn visit_block(&mut self, statements: &[Stmt]);
+    fn visit_block(&mut self, statements: &[Stmt], span: &Span);
     fn visit_expression(&mut self, expression: &Expr);
-    fn visit_var(&mut self, name: &TokenType, initializer: &Option<Expr>);
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>);
     fn visit_while(&mut self, condition: &Expr, body: &Stmt);
     fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>);
-    fn visit_function(&mut self, name: &TokenType, params: &[TokenType], body: &Stmt);
-    fn visit_return(&mut self, value: &Option<Expr>);
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Stmt);
+    fn visit_return(&mut self, keyword: &Token, value: &Option<Expr>);
+    fn visit_import(&mut self, path: &Token, alias: &Option<Token>, names: &[Token]);
     // fn visit_class(&mut self, name: &Token, methods: &[Stmt]);
 }
 
@@ -396,17 +525,37 @@ impl Display for ErrorValue {
 
 pub struct StmtEvaluator {
     m_env: Rc<RefCell<Environment>>,
+    m_hook: Hook,
     m_errors: Vec<ErrorValue>,
+    m_error_span: Option<Span>,
 }
 
 impl StmtEvaluator {
-    pub fn new(env: &Rc<RefCell<Environment>>) -> Self {
+    pub fn new(env: &Rc<RefCell<Environment>>, hook: &Hook) -> Self {
         Self {
             m_env: env.clone(),
+            m_hook: hook.clone(),
             m_errors: Vec::new(),
+            m_error_span: None,
         }
     }
 
+    pub fn execute(&mut self, stmt: &Stmt) {
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_statement(stmt, &self.m_env);
+        }
+
+        if !self.is_stopped() {
+            stmt.accept(self);
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.m_hook
+            .as_ref()
+            .is_some_and(|hook| hook.borrow().is_stopped())
+    }
+
     pub fn get_result(&mut self) -> Result<(), Vec<ErrorValue>> {
         if self.m_errors.is_empty() {
             Ok(())
@@ -414,112 +563,163 @@ impl StmtEvaluator {
             Err(self.m_errors.clone())
         }
     }
+
+    /// The span of the expression the first error came from, see `ExprEvaluator::get_error_span`.
+    pub fn get_error_span(&self) -> Option<Span> {
+        self.m_error_span
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_error(&message);
+        }
+
+        self.fail(Some(span), [ErrorValue::Error(message)]);
+    }
+
+    /// Takes on the errors of a nested expression or statement, raised at `span`.
+    fn fail(&mut self, span: Option<Span>, errors: impl IntoIterator<Item = ErrorValue>) {
+        self.m_error_span = self.m_error_span.or(span);
+        self.m_errors.extend(errors);
+    }
+
+    fn branch(&self, span: Span, taken: bool) {
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_branch(span, taken, &self.m_env);
+        }
+    }
 }
 
 impl StmtVisitor for StmtEvaluator {
-    fn visit_block(&mut self, statements: &[Stmt]) {
+    fn visit_block(&mut self, statements: &[Stmt], _span: &Span) {
         let block_scope = Environment::new_scope(&self.m_env);
         for stmt in statements.iter() {
-            let mut visitor = StmtEvaluator::new(&block_scope);
-            stmt.accept(&mut visitor);
+            let mut visitor = StmtEvaluator::new(&block_scope, &self.m_hook);
+            visitor.execute(stmt);
+            // a `return` or an error ends the block
             if let Err(err) = visitor.get_result() {
-                self.m_errors.extend(err);
+                self.fail(visitor.get_error_span(), err);
+                break;
             }
         }
     }
 
     fn visit_expression(&mut self, expression: &Expr) {
-        let mut visitor = ExprEvaluator::new(&self.m_env);
+        let mut visitor = ExprEvaluator::new(&self.m_env, &self.m_hook);
         expression.accept(&mut visitor);
         if let Err(err) = visitor.get_result() {
-            self.m_errors.extend(err.into_iter().map(ErrorValue::Error));
+            self.fail(
+                visitor.get_error_span(),
+                err.into_iter().map(ErrorValue::Error),
+            );
         }
     }
 
-    fn visit_var(&mut self, name: &TokenType, initializer: &Option<Expr>) {
-        let mut visitor = ExprEvaluator::new(&self.m_env);
+    fn visit_var(&mut self, name: &Token, initializer: &Option<Expr>) {
+        let mut visitor = ExprEvaluator::new(&self.m_env, &self.m_hook);
         if let Some(initializer) = initializer {
             initializer.accept(&mut visitor);
         }
         match visitor.get_result() {
             Ok(result) => {
-                if let TokenType::Identifier(name) = name {
+                if let TokenType::Identifier(name) = name.get_token_type() {
                     // let inner_scope = Environment::new_scope(&self.m_env);
+                    if let Some(hook) = &self.m_hook {
+                        hook.borrow_mut().on_define(name, &result);
+                    }
                     self.m_env.borrow_mut().define(name.to_string(), result);
                     // for stmt in statements.iter() {
                     //     let mut visitor = StmtEvaluator::new(&inner_scope);
                     //     stmt.accept(&mut visitor);
                     //     if let Err(err) = visitor.get_result() {
-                    //         self.m_errors.extend(err);
+                    //         self.fail(visitor.get_error_span(), err);
                     //     }
                     // }
                 }
             }
-            Err(err) => self.m_errors.extend(err.into_iter().map(ErrorValue::Error)),
+            Err(err) => self.fail(
+                visitor.get_error_span(),
+                err.into_iter().map(ErrorValue::Error),
+            ),
         }
     }
 
     fn visit_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: &Option<Box<Stmt>>) {
-        let mut visitor = ExprEvaluator::new(&self.m_env);
+        let mut visitor = ExprEvaluator::new(&self.m_env, &self.m_hook);
         condition.accept(&mut visitor);
         match visitor.get_result() {
             Ok(result) => {
+                self.branch(condition.span(), result.is_truthy());
                 let inner_scope = Environment::new_scope(&self.m_env);
                 if result.is_truthy() {
-                    let mut visitor = StmtEvaluator::new(&inner_scope);
-                    then_branch.accept(&mut visitor);
+                    let mut visitor = StmtEvaluator::new(&inner_scope, &self.m_hook);
+                    visitor.execute(then_branch);
                     if let Err(err) = visitor.get_result() {
-                        self.m_errors.extend(err)
+                        self.fail(visitor.get_error_span(), err)
                     }
                 } else if let Some(else_branch) = else_branch {
-                    let mut visitor = StmtEvaluator::new(&inner_scope);
-                    else_branch.accept(&mut visitor);
+                    let mut visitor = StmtEvaluator::new(&inner_scope, &self.m_hook);
+                    visitor.execute(else_branch);
                     if let Err(err) = visitor.get_result() {
-                        self.m_errors.extend(err)
+                        self.fail(visitor.get_error_span(), err)
                     }
                 }
             }
-            Err(err) => self.m_errors.extend(err.into_iter().map(ErrorValue::Error)),
+            Err(err) => self.fail(
+                visitor.get_error_span(),
+                err.into_iter().map(ErrorValue::Error),
+            ),
         }
     }
 
     fn visit_while(&mut self, condition: &Expr, body: &Stmt) {
-        while {
-            let mut visitor = ExprEvaluator::new(&self.m_env);
+        while !self.is_stopped() && {
+            let mut visitor = ExprEvaluator::new(&self.m_env, &self.m_hook);
             condition.accept(&mut visitor);
             match visitor.get_result() {
-                Ok(result) => result.is_truthy(),
+                Ok(result) => {
+                    self.branch(condition.span(), result.is_truthy());
+                    result.is_truthy()
+                }
                 Err(err) => {
-                    self.m_errors.extend(err.into_iter().map(ErrorValue::Error));
+                    self.fail(
+                        visitor.get_error_span(),
+                        err.into_iter().map(ErrorValue::Error),
+                    );
                     false
                 }
             }
         } {
             let inner_scope = Environment::new_scope(&self.m_env);
-            let mut visitor = StmtEvaluator::new(&inner_scope);
-            body.accept(&mut visitor);
+            let mut visitor = StmtEvaluator::new(&inner_scope, &self.m_hook);
+            visitor.execute(body);
             if let Err(err) = visitor.get_result() {
-                self.m_errors.extend(err)
+                self.fail(visitor.get_error_span(), err);
+                break;
             }
         }
     }
 
-    fn visit_function(&mut self, name: &TokenType, params: &[TokenType], body: &Stmt) {
+    fn visit_function(&mut self, name: &Token, params: &[Token], body: &Stmt) {
         let callable = Value::Callable(Callable::Function(
             Some(self.m_env.clone()),
             params.to_vec(),
             params.len(),
             Box::new(body.clone()),
+            Some(name.clone()),
         ));
 
         // println!("{:?}", callable);
+        if let Some(hook) = &self.m_hook {
+            hook.borrow_mut().on_define(&format!("{}", name), &callable);
+        }
         self.m_env
             .borrow_mut()
             .define(format!("{}", name), callable.clone());
     }
 
-    fn visit_return(&mut self, value: &Option<Expr>) {
-        let mut visitor = ExprEvaluator::new(&self.m_env);
+    fn visit_return(&mut self, _keyword: &Token, value: &Option<Expr>) {
+        let mut visitor = ExprEvaluator::new(&self.m_env, &self.m_hook);
         if let Some(value) = value {
             value.accept(&mut visitor);
         }
@@ -528,7 +728,48 @@ impl StmtVisitor for StmtEvaluator {
             Ok(result) => {
                 self.m_errors.push(ErrorValue::Return(result));
             }
-            Err(err) => self.m_errors.extend(err.into_iter().map(ErrorValue::Error)),
+            Err(err) => self.fail(
+                visitor.get_error_span(),
+                err.into_iter().map(ErrorValue::Error),
+            ),
+        }
+    }
+
+    fn visit_import(&mut self, path: &Token, alias: &Option<Token>, names: &[Token]) {
+        let span = path.get_span();
+        let TokenType::String(path) = path.get_token_type() else {
+            return;
+        };
+
+        let module = match import(path, &self.m_env, &self.m_hook) {
+            Ok(module) => module,
+            Err(err) => {
+                self.error(span, err);
+                return;
+            }
+        };
+
+        let mut bindings = Vec::new();
+        if let Some(alias) = alias {
+            bindings.push((format!("{}", alias), Value::Module(module.clone())));
+        }
+        for token in names {
+            let name = format!("{}", token);
+            match module.get(&name) {
+                Some(value) => bindings.push((name, value)),
+                None => {
+                    let message = format!("Undefined export => {}.{}", module.get_name(), name);
+                    self.error(token.get_span(), message);
+                    return;
+                }
+            }
+        }
+
+        for (name, value) in bindings {
+            if let Some(hook) = &self.m_hook {
+                hook.borrow_mut().on_define(&name, &value);
+            }
+            self.m_env.borrow_mut().define(name, value);
         }
     }
 }
@@ -0,0 +1,114 @@
+use std::process::Command;
+
+fn rlox(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .output()
+        .expect("failed to run rlox")
+}
+
+#[test]
+fn clox_mode_follows_reference_semantics() {
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/clox.lox");
+    std::fs::write(
+        script,
+        concat!(
+            "var a = nil or \"default\";\n",
+            "print a;\n",
+            "print 0 and \"zero is truthy\";\n",
+            "print !0;\n",
+            "if (a) print \"bodies need no braces\";\n",
+            "print missing;\n",
+            "print \"unreachable\";\n",
+        ),
+    )
+    .unwrap();
+
+    let output = rlox(&["--compat=clox", script]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "default\nzero is truthy\nfalse\nbodies need no braces\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Undefined variable => missing\n"
+    );
+
+    // the same script is not rlox
+    let output = rlox(&[script]);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Parser produced"));
+}
+
+#[test]
+fn conformance_report_names_each_difference() {
+    let output = rlox(&[
+        "conformance",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/conformance"),
+    ]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    let (scripts, summary) = report.split_once("\nby feature:\n").expect(&report);
+
+    // each script passes, or differs and says how on the indented lines after it
+    let (mut passed, mut differing) = (Vec::new(), Vec::new());
+    let mut lines = scripts.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(name) = line.strip_prefix("PASS ") {
+            passed.push(name);
+        } else if let Some(name) = line.strip_prefix("DIFF ") {
+            let mut details = 0;
+            while lines.next_if(|line| line.starts_with("    ")).is_some() {
+                details += 1;
+            }
+            assert!(details > 0, "{} has no details in\n{}", name, report);
+            differing.push(name);
+        } else {
+            panic!("unexpected line {:?} in\n{}", line, report);
+        }
+    }
+
+    for conforming in [
+        "logical_operator/and.lox",
+        "logical_operator/or_truth.lox",
+        "operator/not.lox",
+        "if/truth.lox",
+        "while/syntax.lox",
+        "for/syntax.lox",
+        "function/recursion.lox",
+        "return/after_while.lox",
+        "return/in_function.lox",
+    ] {
+        assert!(
+            passed.contains(&conforming),
+            "{} in\n{}",
+            conforming,
+            report
+        );
+    }
+
+    // the per-feature counts add up to the total, and each difference is listed by its kind
+    let (features, rest) = summary.split_once("\n\n").expect(&report);
+    let (conforming, total) = features.lines().fold((0, 0), |(conforming, total), line| {
+        let counts = line.split_whitespace().last().unwrap();
+        let (passed, of) = counts.split_once('/').expect(line);
+        (
+            conforming + passed.parse::<usize>().unwrap(),
+            total + of.parse::<usize>().unwrap(),
+        )
+    });
+    assert_eq!(conforming, passed.len());
+    assert_eq!(total, passed.len() + differing.len());
+    assert!(
+        report.ends_with(&format!("\n{} of {} tests conform\n", conforming, total)),
+        "{}",
+        report
+    );
+
+    let kinds = rest.strip_prefix("by difference:\n").unwrap_or_default();
+    for name in &differing {
+        assert!(kinds.contains(name), "{} not grouped in\n{}", name, report);
+    }
+    for stuck in ["timed out", "crashed"] {
+        assert!(!kinds.contains(stuck), "{}", report);
+    }
+}
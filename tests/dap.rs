@@ -0,0 +1,340 @@
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const PROGRAM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data/debug.txt");
+
+struct Client {
+    m_child: Child,
+    m_stdin: ChildStdin,
+    m_stdout: BufReader<ChildStdout>,
+    m_next_seq: u64,
+    m_events: VecDeque<Value>,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to start the debug adapter");
+
+        Client {
+            m_stdin: child.stdin.take().unwrap(),
+            m_stdout: BufReader::new(child.stdout.take().unwrap()),
+            m_child: child,
+            m_next_seq: 1,
+            m_events: VecDeque::new(),
+        }
+    }
+
+    /// Starts a session on the test program, stopping at the given breakpoints.
+    fn launch(breakpoints: &[u64], stop_on_entry: bool) -> Client {
+        let mut client = Client::start();
+        client.request("initialize", json!({ "adapterID": "rlox" }));
+        client.event("initialized");
+        client.request(
+            "launch",
+            json!({ "program": PROGRAM, "stopOnEntry": stop_on_entry }),
+        );
+        client.request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": PROGRAM },
+                "breakpoints": breakpoints.iter().map(|line| json!({ "line": line })).collect::<Vec<_>>(),
+            }),
+        );
+        client.request("configurationDone", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(
+            self.m_stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.m_stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            self.m_stdout.read_line(&mut header).unwrap();
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", length)) => content_length = length.parse().unwrap(),
+                _ => break,
+            }
+        }
+
+        let mut content = vec![0; content_length];
+        self.m_stdout.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn send_request(&mut self, command: &str, arguments: Value) -> u64 {
+        let seq = self.m_next_seq;
+        self.m_next_seq += 1;
+        self.send(
+            json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments }),
+        );
+        seq
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.send_request(command, arguments);
+
+        loop {
+            let message = self.receive();
+            if message["type"] == "response" && message["request_seq"] == seq {
+                assert_eq!(message["success"], true, "{}", message);
+                return message["body"].clone();
+            }
+            self.m_events.push_back(message);
+        }
+    }
+
+    fn event(&mut self, event: &str) -> Value {
+        if let Some(index) = self.m_events.iter().position(|e| e["event"] == event) {
+            return self.m_events.remove(index).unwrap()["body"].clone();
+        }
+
+        loop {
+            let message = self.receive();
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+            self.m_events.push_back(message);
+        }
+    }
+
+    /// Waits for the next stop and returns its reason with the innermost frame's name and line.
+    fn stopped(&mut self) -> (String, String, u64) {
+        let stopped = self.event("stopped");
+        let reason = stopped["reason"].as_str().unwrap().to_string();
+        let frames = self.request("stackTrace", json!({ "threadId": 1 }));
+        let frame = &frames["stackFrames"][0];
+        (
+            reason,
+            frame["name"].as_str().unwrap().to_string(),
+            frame["line"].as_u64().unwrap(),
+        )
+    }
+
+    fn evaluate(&mut self, expression: &str, frame: u64) -> Value {
+        self.request(
+            "evaluate",
+            json!({ "expression": expression, "frameId": frame }),
+        )["result"]
+            .clone()
+    }
+
+    fn disconnect(mut self) -> i32 {
+        self.request("disconnect", json!({}));
+        self.m_child.wait().unwrap().code().unwrap()
+    }
+}
+
+fn stop(reason: &str, name: &str, line: u64) -> (String, String, u64) {
+    (reason.into(), name.into(), line)
+}
+
+#[test]
+fn stops_at_breakpoints_and_inspects_frames() {
+    let mut client = Client::launch(&[3], false);
+    assert_eq!(client.stopped(), stop("breakpoint", "add", 3));
+
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(frames["totalFrames"], 2);
+    assert_eq!(frames["stackFrames"][1]["name"], "<script>");
+    assert_eq!(frames["stackFrames"][1]["line"], 10);
+    assert_eq!(frames["stackFrames"][1]["source"]["path"], PROGRAM);
+
+    let scopes = client.request("scopes", json!({ "frameId": 0 }));
+    let names: Vec<_> = scopes["scopes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|scope| scope["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Locals", "Scope 1", "Globals"]);
+
+    let locals = scopes["scopes"][0]["variablesReference"].clone();
+    let variables = client.request("variables", json!({ "variablesReference": locals }));
+    assert_eq!(
+        variables["variables"],
+        json!([{ "name": "sum", "value": "0", "variablesReference": 0 }])
+    );
+
+    assert_eq!(client.evaluate("a + b + 10", 0), "10");
+    assert_eq!(client.evaluate("i", 1), "0");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), stop("breakpoint", "add", 3));
+    assert_eq!(client.evaluate("sum", 0), "1");
+
+    client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": PROGRAM }, "breakpoints": [] }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("output")["output"], "3\n");
+    assert_eq!(client.event("exited")["exitCode"], 0);
+    client.event("terminated");
+
+    assert_eq!(client.disconnect(), 0);
+}
+
+#[test]
+fn steps_in_over_and_out() {
+    let mut client = Client::launch(&[], true);
+    assert_eq!(client.stopped(), stop("entry", "<script>", 1));
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), stop("step", "<script>", 7));
+    for line in [8, 9, 10] {
+        client.request("next", json!({ "threadId": 1 }));
+        assert_eq!(client.stopped(), stop("step", "<script>", line));
+    }
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), stop("step", "add", 2));
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), stop("step", "<script>", 11));
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), stop("step", "<script>", 10));
+
+    assert_eq!(client.disconnect(), 0);
+}
+
+#[test]
+fn replays_a_recorded_session() {
+    let mut client = Client::start();
+
+    // requests are sent up front, as a recorded session would be, and the adapter answers them
+    // as the program reaches each stop
+    let script = [
+        ("initialize", json!({ "adapterID": "rlox" })),
+        ("launch", json!({ "program": PROGRAM })),
+        (
+            "setBreakpoints",
+            json!({ "source": { "path": PROGRAM }, "breakpoints": [{ "line": 11 }] }),
+        ),
+        ("configurationDone", json!({})),
+        ("evaluate", json!({ "expression": "total", "frameId": 0 })),
+        ("evaluate", json!({ "expression": "undefined_name" })),
+        ("continue", json!({ "threadId": 1 })),
+        ("evaluate", json!({ "expression": "total" })),
+        (
+            "setBreakpoints",
+            json!({ "source": { "path": PROGRAM }, "breakpoints": [] }),
+        ),
+        ("continue", json!({ "threadId": 1 })),
+        ("disconnect", json!({})),
+    ];
+    for (command, arguments) in script {
+        client.send_request(command, arguments);
+    }
+
+    let mut transcript = Vec::new();
+    loop {
+        let message = client.receive();
+        let summary = match message["type"].as_str().unwrap() {
+            "response" => format!(
+                "{} {} {}",
+                message["command"].as_str().unwrap(),
+                message["success"],
+                message["body"]["result"].as_str().unwrap_or_default()
+            ),
+            _ => format!("event {}", message["event"].as_str().unwrap()),
+        };
+        transcript.push(summary.trim_end().to_string());
+        if message["command"] == "disconnect" {
+            break;
+        }
+    }
+
+    assert_eq!(
+        transcript,
+        [
+            "initialize true",
+            "event initialized",
+            "launch true",
+            "setBreakpoints true",
+            "configurationDone true",
+            "event stopped",
+            "evaluate true 0",
+            "evaluate false",
+            "continue true",
+            "event stopped",
+            "evaluate true 1",
+            "setBreakpoints true",
+            "continue true",
+            "event output",
+            "event exited",
+            "event terminated",
+            "disconnect true",
+        ]
+    );
+    assert_eq!(client.m_child.wait().unwrap().code(), Some(0));
+}
+
+#[test]
+fn verifies_only_breakpoints_on_statements() {
+    let mut client = Client::start();
+    client.request("initialize", json!({ "adapterID": "rlox" }));
+    client.request("launch", json!({ "program": PROGRAM }));
+
+    // a statement, a blank line, a comment and a line past the end
+    let body = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": PROGRAM },
+            "breakpoints": [{ "line": 3 }, { "line": 5 }, { "line": 6 }, { "line": 40 }],
+        }),
+    );
+    let verified: Vec<_> = body["breakpoints"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|breakpoint| {
+            (
+                breakpoint["line"].as_u64().unwrap(),
+                breakpoint["verified"] == true,
+            )
+        })
+        .collect();
+    assert_eq!(verified, [(3, true), (5, false), (6, false), (40, false)]);
+
+    // only the main program takes breakpoints
+    let body = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data/test.txt") },
+            "breakpoints": [{ "line": 1 }],
+        }),
+    );
+    assert_eq!(body["breakpoints"][0]["verified"], false);
+
+    assert_eq!(client.disconnect(), 0);
+}
+
+#[test]
+fn disconnecting_while_paused_stops_the_program() {
+    let mut client = Client::launch(&[3], false);
+    assert_eq!(client.stopped(), stop("breakpoint", "add", 3));
+    client.request("disconnect", json!({}));
+
+    // the rest of the program does not run, so nothing more is printed or reported
+    let mut rest = String::new();
+    client.m_stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(client.m_child.wait().unwrap().code(), Some(0));
+}
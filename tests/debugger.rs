@@ -0,0 +1,118 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const PROGRAM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data/debug.txt");
+
+fn debug(commands: &str) -> Output {
+    debug_program(PROGRAM, commands)
+}
+
+fn debug_program(program: &str, commands: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["debug", program])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start the debugger");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn stops_at_breakpoints_and_steps() {
+    let output = debug("b 2\nc\np a + b\nbt\nn\nn\nd 2\nc\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "stopped in <script> at line 1\n",
+            "    1 | fun add(a, b) {\n",
+            "(debug) breakpoint set at line 2\n",
+            "(debug) stopped in add at line 2\n",
+            "    2 |   let sum = a + b;\n",
+            "(debug) 0\n",
+            "(debug) * #0 add at line 2\n",
+            "  #1 <script> at line 10\n",
+            "(debug) stopped in add at line 3\n",
+            "    3 |   return sum;\n",
+            "(debug) stopped in <script> at line 11\n",
+            "   11 |   i = i + 1;\n",
+            "(debug) breakpoint removed from line 2\n",
+            "(debug) 3\n",
+            "program finished\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn quitting_stops_the_program() {
+    let output = debug("b 10\nc\nq\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.ends_with("   10 |   total = add(total, i);\n(debug) "),
+        "{}",
+        stdout
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    // running out of commands quits as well, rather than running the rest unattended
+    let output = debug("s\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("program finished"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn steps_into_functions_of_a_module() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/debug_module");
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(
+        format!("{}/util.lox", dir),
+        "fun twice(x) {\n  let y = x * 2;\n  return y;\n}\n",
+    )
+    .unwrap();
+    let program = format!("{}/main.lox", dir);
+    std::fs::write(
+        &program,
+        "import \"util.lox\" as util;\nprintln(util.twice(4));\n",
+    )
+    .unwrap();
+
+    let output = debug_program(
+        &program,
+        "n
+n
+s
+bt
+l
+c
+",
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "stopped in <script> at line 1\n",
+            "    1 | import \"util.lox\" as util;\n",
+            "(debug) stopped in <script> at line 1 of util.lox\n",
+            "    1 | fun twice(x) {\n",
+            "(debug) stopped in <script> at line 2\n",
+            "    2 | println(util.twice(4));\n",
+            "(debug) stopped in twice at line 2 of util.lox\n",
+            "    2 |   let y = x * 2;\n",
+            "(debug) * #0 twice at line 2 of util.lox\n",
+            "  #1 <script> at line 2\n",
+            "(debug)      1 | fun twice(x) {\n",
+            ">    2 |   let y = x * 2;\n",
+            "     3 |   return y;\n",
+            "     4 | }\n",
+            "     5 | \n",
+            "(debug) 8\n",
+            "program finished\n",
+        )
+    );
+}
@@ -0,0 +1,255 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const URI: &str = "file:///tmp/test.lox";
+
+const SOURCE: &str = "fun add(a, b) {
+  let sum = a + b;
+  return sum;
+}
+
+let total = add(1, 2);
+println(total);
+";
+
+struct Client {
+    m_child: Child,
+    m_stdin: ChildStdin,
+    m_stdout: BufReader<ChildStdout>,
+    m_next_id: u64,
+    m_notifications: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to start the language server");
+
+        let mut client = Client {
+            m_stdin: child.stdin.take().unwrap(),
+            m_stdout: BufReader::new(child.stdout.take().unwrap()),
+            m_child: child,
+            m_next_id: 1,
+            m_notifications: Vec::new(),
+        };
+
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["definitionProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(
+            self.m_stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.m_stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            self.m_stdout.read_line(&mut header).unwrap();
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", length)) => content_length = length.parse().unwrap(),
+                _ => break,
+            }
+        }
+
+        let mut content = vec![0; content_length];
+        self.m_stdout.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.m_next_id;
+        self.m_next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                return message["result"].clone();
+            }
+            self.m_notifications.push(message);
+        }
+    }
+
+    fn open(&mut self, text: &str) -> Value {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": text },
+            }),
+        );
+        self.receive()
+    }
+
+    fn at(&mut self, method: &str, line: u64, character: u64) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            }),
+        )
+    }
+
+    fn shutdown(mut self) -> i32 {
+        assert_eq!(self.request("shutdown", Value::Null), Value::Null);
+        self.notify("exit", Value::Null);
+        self.m_child.wait().unwrap().code().unwrap()
+    }
+}
+
+fn range(start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 },
+    })
+}
+
+#[test]
+fn publishes_diagnostics_on_change() {
+    let mut client = Client::start();
+
+    let diagnostics = client.open(SOURCE);
+    assert_eq!(diagnostics["method"], "textDocument/publishDiagnostics");
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "let x = (1;\n" }],
+        }),
+    );
+    let diagnostics = client.receive();
+    let diagnostics = diagnostics["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 10 })
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 3 },
+            "contentChanges": [{ "text": "return 1;\n" }],
+        }),
+    );
+    let diagnostics = client.receive();
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["message"],
+        "Cannot return from top-level code"
+    );
+
+    assert_eq!(client.shutdown(), 0);
+}
+
+#[test]
+fn finds_definitions_and_references() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let definition = client.at("textDocument/definition", 5, 13);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(definition["range"], range((0, 4), (0, 7)));
+
+    let definition = client.at("textDocument/definition", 2, 10);
+    assert_eq!(definition["range"], range((1, 6), (1, 9)));
+
+    let references = client.at("textDocument/references", 0, 12);
+    assert_eq!(
+        references,
+        json!([
+            { "uri": URI, "range": range((0, 11), (0, 12)) },
+            { "uri": URI, "range": range((1, 16), (1, 17)) },
+        ])
+    );
+
+    assert_eq!(client.shutdown(), 0);
+}
+
+#[test]
+fn hovers_with_function_signatures() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let hover = client.at("textDocument/hover", 5, 12);
+    assert_eq!(hover["contents"]["value"], "```lox\nfun add(a, b)\n```");
+
+    let hover = client.at("textDocument/hover", 4, 0);
+    assert_eq!(hover, Value::Null);
+
+    assert_eq!(client.shutdown(), 0);
+}
+
+#[test]
+fn lists_document_symbols() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let symbols = symbols.as_array().unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0]["name"], "add");
+    assert_eq!(symbols[0]["range"], range((0, 4), (3, 1)));
+    assert_eq!(symbols[0]["children"][0]["name"], "sum");
+    assert_eq!(symbols[1]["name"], "total");
+
+    assert_eq!(client.shutdown(), 0);
+}
+
+#[test]
+fn completes_names_in_scope() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let labels = |items: Value| -> Vec<String> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let inside = labels(client.at("textDocument/completion", 2, 2));
+    for name in ["sum", "a", "b", "add", "total", "while", "import"] {
+        assert!(inside.contains(&name.to_string()), "missing {}", name);
+    }
+
+    let outside = labels(client.at("textDocument/completion", 6, 0));
+    assert!(outside.contains(&"total".to_string()));
+    assert!(!outside.contains(&"sum".to_string()));
+
+    assert_eq!(client.shutdown(), 0);
+}
+
+#[test]
+fn exits_with_an_error_without_shutdown() {
+    let mut client = Client::start();
+    client.notify("exit", Value::Null);
+    assert_eq!(client.m_child.wait().unwrap().code(), Some(1));
+}
@@ -0,0 +1,143 @@
+use std::process::Command;
+
+fn rlox(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .output()
+        .expect("failed to run rlox")
+}
+
+#[test]
+fn both_parsers_build_the_same_trees() {
+    let output = rlox(&[
+        "compare-parsers",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data"),
+    ]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+    assert!(!report.contains("DIFF"), "{}", report);
+
+    let output = rlox(&[
+        "--compat=clox",
+        "compare-parsers",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/conformance"),
+    ]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+}
+
+#[test]
+fn both_parsers_report_the_same_errors() {
+    let source = concat!(
+        "let a = 1\n",
+        "println(a b);\n",
+        "fun (x) { return x; }\n",
+        "println(\"${a b} and ${}\");\n",
+        "let s = \"open;\n",
+    );
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/parse_errors.lox");
+    std::fs::write(script, source).unwrap();
+
+    let output = rlox(&["compare-parsers", script]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let nom = rlox(&["--parser=nom", script]);
+    let handwritten = rlox(&["--parser=handwritten", script]);
+    assert_eq!(nom.stdout, handwritten.stdout);
+    assert_eq!(handwritten.status.code(), Some(65));
+}
+
+#[test]
+fn handwritten_parser_runs_scripts() {
+    let output = rlox(&[
+        "--parser=handwritten",
+        "test",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data"),
+    ]);
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+    assert!(!report.contains("FAIL"), "{}", report);
+}
+
+#[test]
+fn operators_are_told_apart_from_comments_and_longer_operators() {
+    // no newline after the final comment
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/operators.lox");
+    std::fs::write(
+        script,
+        concat!(
+            "let a = 10;\n",
+            "println(a / 2);\n",
+            "println( - a);\n",
+            "println(!\ttrue);\n",
+            "println(a == 10);\n",
+            "// the end",
+        ),
+    )
+    .unwrap();
+
+    // `//` is not a division and `==` is not an initializer, so each line only lacks its `;`
+    let errors = concat!(env!("CARGO_TARGET_TMPDIR"), "/operator_errors.lox");
+    std::fs::write(errors, "let b = a // no semicolon\nlet c == 1;\n").unwrap();
+
+    for parser in ["--parser=nom", "--parser=handwritten"] {
+        let output = rlox(&[parser, script]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "5\n-10\nfalse\ntrue\n",
+            "{}",
+            parser
+        );
+        assert!(output.status.success(), "{}", parser);
+
+        let output = rlox(&[parser, errors]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            concat!(
+                "Parser produced 2 errors:\n",
+                "    ERROR: Expected ';' after variable declaration => line 1 | column 11\n",
+                "    ERROR: Expected ';' after variable declaration => line 2 | column 7\n",
+            ),
+            "{}",
+            parser
+        );
+    }
+}
+
+#[test]
+fn errors_after_a_broken_body_are_reported() {
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/broken_bodies.lox");
+    std::fs::write(
+        script,
+        concat!(
+            "fun f(a,) { return a; }\n",
+            "println(1 +);\n",
+            "if (x { println(1); }\n",
+            "let y = ;\n",
+            "let z = 1 2\n",
+            "println(z);\n",
+        ),
+    )
+    .unwrap();
+
+    for parser in ["--parser=nom", "--parser=handwritten"] {
+        let output = rlox(&[parser, script]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            concat!(
+                "Parser produced 5 errors:\n",
+                "    ERROR: Expected parameter name after ',' => line 1 | column 9\n",
+                "    ERROR: Expected expression after operator => line 2 | column 12\n",
+                "    ERROR: Expected ')' after if condition => line 3 | column 7\n",
+                "    ERROR: Expected expression after '=' => line 4 | column 9\n",
+                "    ERROR: Expected ';' after variable declaration => line 5 | column 11\n",
+            ),
+            "{}",
+            parser
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn repl(home: &str, input: &str) -> Output {
+    std::fs::create_dir_all(home).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the repl");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs the repl in a pseudo-terminal, so that line editing is on, and types each of `keys` once
+/// the repl has finished drawing. Returns everything it drew.
+fn repl_in_terminal(home: &str, keys: &[&str]) -> String {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::FromRawFd;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Duration;
+
+    fn settle(drawn: &Receiver<Vec<u8>>, output: &mut Vec<u8>) {
+        while let Ok(bytes) = drawn.recv_timeout(Duration::from_millis(300)) {
+            output.extend(bytes);
+        }
+    }
+
+    std::fs::create_dir_all(home).unwrap();
+    let (mut master, mut slave) = (0, 0);
+    let size = libc::winsize {
+        ws_row: 24,
+        ws_col: 200,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        )
+    };
+    assert_eq!(opened, 0, "could not open a pseudo-terminal");
+    let (mut terminal, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .env("HOME", home)
+        .env("TERM", "xterm")
+        .current_dir(home)
+        .stdin(slave.try_clone().unwrap())
+        .stdout(slave.try_clone().unwrap())
+        .stderr(slave)
+        .spawn()
+        .expect("failed to start the repl");
+
+    let (sender, drawn) = channel();
+    let mut reader = terminal.try_clone().unwrap();
+    std::thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        // reading fails once the repl has exited and closed the terminal
+        while let Ok(read @ 1..) = reader.read(&mut buffer) {
+            let _ = sender.send(buffer[..read].to_vec());
+        }
+    });
+
+    let mut output = Vec::new();
+    for keys in keys {
+        settle(&drawn, &mut output);
+        terminal.write_all(keys.as_bytes()).unwrap();
+    }
+    child.wait().unwrap();
+    output.extend(drawn.iter().flatten());
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+#[test]
+fn incomplete_input_continues_on_the_next_line() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_multiline");
+    let output = repl(
+        home,
+        concat!(
+            "fun double(x) {\n",
+            "  return x * 2;\n",
+            "}\n",
+            "println(double(\n",
+            "21));\n",
+            "println(\"one\n",
+            "two\");\n",
+            "println(\"\"\"\n",
+            "say \"three\"\n",
+            "\"\"\");\n",
+            "quit\n",
+        ),
+    );
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("42\n"), "{}", stdout);
+    assert!(stdout.contains("one\ntwo\n"), "{}", stdout);
+    assert!(stdout.contains("say \"three\"\n"), "{}", stdout);
+    assert!(!stdout.contains("ERROR"), "{}", stdout);
+}
+
+#[test]
+fn a_blank_line_reports_unfinished_input_and_history_is_saved() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_history");
+    let _ = std::fs::remove_file(format!("{}/.rlox_history", home));
+    let output = repl(home, "broken(\n\nprintln(\"after\");\n");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("ERROR"), "{}", stdout);
+    assert!(stdout.contains("after\n"), "{}", stdout);
+
+    let history = std::fs::read_to_string(format!("{}/.rlox_history", home)).unwrap();
+    assert!(history.contains("println(\"after\");"), "{}", history);
+}
+
+#[test]
+fn bare_expressions_echo_their_value() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_echo");
+    let output = repl(
+        home,
+        "1 + 2\nlet x = 5;\nx * 2;\n\"s\"\nprintln(\"quiet\")\nquit\n",
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=> 3\n"), "{}", stdout);
+    assert!(stdout.contains("=> 10\n"), "{}", stdout);
+    assert!(stdout.contains("=> \"s\"\n"), "{}", stdout);
+    assert!(stdout.contains("quiet\n"), "{}", stdout);
+    assert!(!stdout.contains("=> nil"), "{}", stdout);
+}
+
+#[test]
+fn meta_commands_inspect_the_session() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_commands");
+    let script = format!("{}/loaded.lox", home);
+    std::fs::create_dir_all(home).unwrap();
+    std::fs::write(&script, "let loaded = \"yes\";\n").unwrap();
+
+    let output = repl(
+        home,
+        &format!(
+            concat!(
+                "let x = 5;\n",
+                ":type x\n",
+                ":type len\n",
+                ":ast -x + 1\n",
+                ":load {}\n",
+                ":env\n",
+                ":time x * 2\n",
+                ":reset\n",
+                ":type x\n",
+                ":bogus\n",
+                "quit\n",
+            ),
+            script
+        ),
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for expected in [
+        "number\n",
+        "native function\n",
+        "Expression\n  Binary +\n    Unary -\n      Variable x\n    Literal 1\n",
+        "loaded = \"yes\"\nx = 5\n",
+        "=> 10\ntime: ",
+        "session reset\n",
+        "Undefined variable => x\n",
+        "Unknown command => :bogus",
+    ] {
+        assert!(stdout.contains(expected), "{:?} in {}", expected, stdout);
+    }
+    assert!(!stdout.contains("PI = "), "{}", stdout);
+}
+
+#[test]
+fn tab_completes_keywords_names_and_module_fields() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_completion");
+    std::fs::create_dir_all(home).unwrap();
+    std::fs::write(
+        format!("{}/shapes.lox", home),
+        "fun square_area(side) { return side * side; }\n",
+    )
+    .unwrap();
+
+    let output = repl_in_terminal(
+        home,
+        &[
+            "fal\t\r",
+            "let total_count = 4;\r",
+            "let total_sum = 1;\r",
+            "total_c\t * 2\r",
+            // repeated tabs go through the candidates in order
+            "total_\t\t + 10\r",
+            "import \"shapes.lox\" as shapes;\r",
+            "shapes.sq\t(3)\r",
+            "quit\r",
+        ],
+    );
+
+    for expected in ["=> false\r\n", "=> 8\r\n", "=> 11\r\n", "=> 9\r\n"] {
+        assert!(output.contains(expected), "{:?} in {:?}", expected, output);
+    }
+    assert!(!output.contains("error"), "{:?}", output);
+}
+
+#[test]
+fn line_editing_highlights_code_and_underlines_errors() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_highlight");
+    let output = repl_in_terminal(
+        home,
+        &[
+            "let n = 4;\r",
+            "\"n is ${n + 1}\"\r",
+            "println(\"(\")\r",
+            "println(1\r",
+            "\r",
+            "missing(\"é\");\r",
+            "fun half(x) { return x / nope; }\r",
+            "1 + half(4);\r",
+            "let é = ;\r",
+            "\r",
+            "quit\r",
+        ],
+    );
+
+    for expected in [
+        // the code inside `${}` is colored as code, the rest as a string
+        "\x1b[32m\"n is ${\x1b[0mn + \x1b[33m1\x1b[0m\x1b[32m}\"\x1b[0m",
+        // the `(` inside the string does not pair with the closing `)`
+        "\x1b[36mprintln\x1b[0m\x1b[1;4m(\x1b[0m\x1b[32m\"(\"\x1b[0m)",
+        // an error at the end of a line points just past it
+        "  println(1\r\n           \x1b[1;31m^\x1b[0m",
+        // widths and offsets count characters rather than bytes
+        // a runtime error points at the expression that failed, not the whole statement
+        "  missing(\"é\");\r\n  \x1b[1;31m^^^^^^^\x1b[0m",
+        // and one raised inside a function at the call of it
+        "  1 + half(4);\r\n      \x1b[1;31m^^^^\x1b[0m",
+        "  let é = ;\r\n          \x1b[1;31m^\x1b[0m",
+    ] {
+        assert!(output.contains(expected), "{:?} in {:?}", expected, output);
+    }
+}
+
+#[test]
+fn loaded_files_import_relative_to_their_directory() {
+    let home = concat!(env!("CARGO_TARGET_TMPDIR"), "/repl_load");
+    let lib = format!("{}/lib", home);
+    std::fs::create_dir_all(&lib).unwrap();
+    std::fs::write(format!("{}/helper.lox", lib), "let answer = 42;\n").unwrap();
+    std::fs::write(
+        format!("{}/entry.lox", lib),
+        "import \"helper.lox\" as helper;\nlet loaded = helper.answer;\n",
+    )
+    .unwrap();
+
+    let output = repl(
+        home,
+        &format!(
+            ":load {}/entry.lox\nloaded\nimport \"helper.lox\" as again;\nquit\n",
+            lib
+        ),
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=> 42\n"), "{}", stdout);
+    // the repl itself still imports from the working directory
+    assert!(
+        stdout.contains("Could not find module => helper.lox"),
+        "{}",
+        stdout
+    );
+}
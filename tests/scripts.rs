@@ -0,0 +1,706 @@
+use std::process::Command;
+
+#[test]
+fn testing_data_matches_expectations() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["test", concat!(env!("CARGO_MANIFEST_DIR"), "/testing_data")])
+        .output()
+        .expect("failed to run the test runner");
+
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", report);
+    assert!(!report.contains("FAIL"), "{}", report);
+}
+
+#[test]
+fn registered_tests_report_failures_and_errors() {
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/registered_tests.lox");
+    std::fs::write(
+        script,
+        concat!(
+            "test(\"passes\", fun () { assert(true); });\n",
+            "test(\"fails\", fun () { assert_eq(1 + 1, 3); });\n",
+            "test(\"errors\", fun () { missing(); });\n",
+            "test(\"runs after\", fun () { assert_ne(\"a\", \"b\"); });\n",
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(script)
+        .output()
+        .expect("failed to run the script");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "test passes ... ok\n",
+            "test fails ... FAILED\n",
+            "    Assertion failed => left: 2, right: 3\n",
+            "test errors ... ERROR\n",
+            "    Undefined variable => missing\n",
+            "test runs after ... ok\n",
+            "\n",
+            "2 passed, 1 failed, 1 errors\n",
+        )
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["test", script])
+        .output()
+        .expect("failed to run the test runner");
+    assert!(!output.status.success());
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        report.contains("test \"fails\" failed: Assertion failed => left: 2, right: 3"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("test \"errors\" errored: Undefined variable => missing"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_runner_finds_tests_by_running_and_times_out_loops() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/test_runner");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+
+    // registered from inside a function, with no `test(` at the start of a line
+    std::fs::write(
+        format!("{}/indirect.lox", dir),
+        concat!(
+            "fun check(name, expected) {\n",
+            "  test (name, fun () { assert_eq(1 + 1, expected); });\n",
+            "}\n",
+            "check(\"adds\", 2);\n",
+            "check(\"adds wrong\", 3);\n",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        format!("{}/loops.lox", dir),
+        "println(\"start\"); // expect: start\nwhile (true) {}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        format!("{}/reads.lox", dir),
+        "let line = read_line();\nwhile (line != \"quit\") { line = read_line(); }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["test", dir])
+        .output()
+        .expect("failed to run the test runner");
+    assert!(!output.status.success());
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(report.contains("FAIL "), "{}", report);
+    assert!(
+        report.contains("test \"adds wrong\" failed: Assertion failed => left: 2, right: 3"),
+        "{}",
+        report
+    );
+    assert!(report.contains("timed out after 2s"), "{}", report);
+    assert!(
+        report.contains("0 passed, 2 failed, 1 skipped without expectations"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn file_natives_read_write_and_report_os_errors() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/file_natives");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+
+    let script = format!("{}/script.lox", dir);
+    std::fs::write(
+        &script,
+        format!(
+            concat!(
+                "let dir = join_path(\"{}\", \"out\");\n",
+                "mkdir(dir);\n",
+                "let report = join_path(dir, \"report.txt\");\n",
+                "write_file(report, \"total: \");\n",
+                "append_file(report, 42);\n",
+                "println(read_file(report));\n",
+                "println(exists(report));\n",
+                "println(list_dir(dir));\n",
+                "println(basename(report));\n",
+                "println(extension(report));\n",
+                "println(basename(dirname(report)));\n",
+                "remove(report);\n",
+                "remove(dir);\n",
+                "println(exists(dir));\n",
+                "read_file(report);\n",
+            ),
+            dir
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(&script)
+        .output()
+        .expect("failed to run the script");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(concat!(
+            "total: 42\n",
+            "true\n",
+            "[\"report.txt\"]\n",
+            "report.txt\n",
+            "txt\n",
+            "out\n",
+            "false\n",
+        )),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Could not read file => ") && stdout.contains("report.txt: No such file"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn scripts_receive_arguments_and_set_the_exit_code() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/exit_codes");
+    std::fs::create_dir_all(dir).unwrap();
+    let run = |name: &str, source: &str, args: &[&str]| {
+        let script = format!("{}/{}.lox", dir, name);
+        std::fs::write(&script, source).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg(&script)
+            .args(args)
+            .env("RLOX_GREETING", "hello")
+            .output()
+            .expect("failed to run the script")
+    };
+
+    let output = run(
+        "args",
+        "println(args);\nprintln(getenv(\"RLOX_GREETING\"));\nexit(len(args));\n",
+        &["one", "--two"],
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "[\"one\", \"--two\"]\nhello\n"
+    );
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = run("runtime", "missing();\nprintln(\"after\");\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("after\n"));
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run("parse", "let = ;\n", &[]);
+    assert_eq!(output.status.code(), Some(65));
+
+    let output = run("success", "println(1);\n", &[]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn programs_run_from_stdin_and_eval_with_a_shebang() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-", "piped"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run rlox");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"#!/usr/bin/env rlox\nprintln(args);\nprintln(;\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=> line 3 | column 9"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(65));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", "println(join(args, \"+\")); missing();", "a", "-b"])
+        .output()
+        .expect("failed to run rlox");
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("a+-b\n"));
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn parse_errors_are_all_reported_with_their_locations() {
+    let source = concat!(
+        "let a = 1\n",
+        "println(a b);\n",
+        "fun f(x) {\n",
+        "  let = 3;\n",
+        "  return x;\n",
+        "}\n",
+        "}\n",
+        "let ok = (2;\n",
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", source])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Parser produced 5 errors:\n",
+            "    ERROR: Expected ';' after variable declaration => line 1 | column 10\n",
+            "    ERROR: Expected ')' after arguments => line 2 | column 11\n",
+            "    ERROR: Expected variable name => line 4 | column 7\n",
+            "    ERROR: Unexpected '}' => line 7 | column 1\n",
+            "    ERROR: Expected ')' after expression => line 8 | column 12\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn reserved_words_cannot_be_used_as_names() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", "let if = 1;\nfun f(and) {}\nlet orange = 2;\n"])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Parser produced 2 errors:\n",
+            "    ERROR: 'if' is a reserved word and cannot be used as a name => line 1 | column 5\n",
+            "    ERROR: 'and' is a reserved word and cannot be used as a name => line 2 | column 7\n",
+        )
+    );
+}
+
+#[test]
+fn invalid_escapes_are_reported_where_they_are() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args([
+            "-e",
+            "let a = \"C:\\quux\";\nlet b = \"\\u{110000}\"\nlet c = r\"C:\\temp\";\n",
+        ])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Parser produced 3 errors:\n",
+            "    ERROR: Invalid escape sequence '\\q' => line 1 | column 12\n",
+            "    ERROR: Invalid unicode escape '\\u{110000}' => line 2 | column 10\n",
+            "    ERROR: Expected ';' after variable declaration => line 2 | column 21\n",
+        )
+    );
+}
+
+#[test]
+fn errors_in_interpolations_are_reported_inside_the_string() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", "let a = 1;\nprintln(\"a is ${a b}, next ${a +}\");\n"])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Parser produced 2 errors:\n",
+            "    ERROR: Expected '}' after expression => line 2 | column 19\n",
+            "    ERROR: Expected expression after operator => line 2 | column 33\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn trace_follows_calls_assignments_and_errors() {
+    let script = concat!(env!("CARGO_TARGET_TMPDIR"), "/trace.lox");
+    std::fs::write(
+        script,
+        concat!(
+            "fun double(x) {\n",
+            "  return x * 2;\n",
+            "}\n",
+            "fun fail() {\n",
+            "  missing();\n",
+            "}\n",
+            "let a = 1;\n",
+            "a = double(a);\n",
+            "fail();\n",
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--trace", script])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        concat!(
+            "[line 1] fun double(x) {\n",
+            "  define double = <fun double>\n",
+            "[line 4] fun fail() {\n",
+            "  define fail = <fun fail>\n",
+            "[line 7] let a = 1;\n",
+            "  define a = 1\n",
+            "[line 8] a = double(a);\n",
+            "  call double(1)\n",
+            "    [line 2] return x * 2;\n",
+            "  return double => 2\n",
+            "  assign a = 2\n",
+            "[line 9] fail();\n",
+            "  call fail()\n",
+            "    [line 5] missing();\n",
+            "      error: Undefined variable => missing\n",
+            "  unwind fail\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn profile_tells_functions_apart_by_declaration() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/profile");
+    std::fs::create_dir_all(dir).unwrap();
+    let script = format!("{}/script.lox", dir);
+    let folded = format!("{}/script.folded", dir);
+    std::fs::write(
+        &script,
+        concat!(
+            "fun step(n) {\n",
+            "  return n + 1;\n",
+            "}\n",
+            "fun outer() {\n",
+            "  fun step(n) {\n",
+            "    return n - 1;\n",
+            "  }\n",
+            "  return step(1);\n",
+            "}\n",
+            "let twice = fun (f) { return f(f(0)); };\n",
+            "twice(step);\n",
+            "outer();\n",
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--profile", "--profile-output", &folded, &script])
+        .output()
+        .expect("failed to run rlox");
+    assert!(output.status.success());
+    let table = String::from_utf8_lossy(&output.stderr);
+    let calls = |name: &str| {
+        table
+            .lines()
+            .find_map(|line| {
+                let mut columns = line.split_whitespace();
+                (columns.next() == Some(name)).then(|| columns.next().unwrap().to_string())
+            })
+            .unwrap_or_else(|| panic!("no row for {} in\n{}", name, table))
+    };
+    assert_eq!(calls("step:1"), "2");
+    assert_eq!(calls("step:5"), "1");
+    assert_eq!(calls("<anonymous:10>"), "1");
+    assert_eq!(calls("outer"), "1");
+
+    // each line is a stack followed by its exclusive time in microseconds
+    let stacks = std::fs::read_to_string(&folded).unwrap();
+    let stacks: Vec<_> = stacks
+        .lines()
+        .map(|line| {
+            let (stack, time) = line.rsplit_once(' ').unwrap();
+            assert!(time.parse::<u128>().is_ok(), "{}", line);
+            stack
+        })
+        .collect();
+    assert_eq!(
+        stacks,
+        [
+            "<script>",
+            "<script>;<anonymous:10>",
+            "<script>;<anonymous:10>;step:1",
+            "<script>;outer",
+            "<script>;outer;step:5",
+        ]
+    );
+}
+
+#[test]
+fn coverage_reports_lines_and_branches() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/coverage");
+    std::fs::create_dir_all(dir).unwrap();
+    let script = format!("{}/script.lox", dir);
+    let lcov = format!("{}/lcov.info", dir);
+    std::fs::write(
+        &script,
+        concat!(
+            "fun sign(n) {\n",
+            "  if (n < 0) {\n",
+            "    return -1;\n",
+            "  }\n",
+            "  return 1;\n",
+            "}\n",
+            "println(sign(2));\n",
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--coverage", "--coverage-output", &lcov, &script])
+        .output()
+        .expect("failed to run rlox");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            concat!(
+                "lines:    4/5 (80.0%)\n",
+                "branches: 1/2 (50.0%)\n",
+                "uncovered lines: 3\n",
+                "lcov tracefile written to {}\n",
+            ),
+            lcov
+        )
+    );
+    assert_eq!(
+        std::fs::read_to_string(&lcov).unwrap(),
+        format!(
+            concat!(
+                "TN:\n",
+                "SF:{}\n",
+                "BRDA:2,0,0,0\n",
+                "BRDA:2,0,1,1\n",
+                "BRF:2\n",
+                "BRH:1\n",
+                "DA:1,1\n",
+                "DA:2,1\n",
+                "DA:3,0\n",
+                "DA:5,1\n",
+                "DA:7,1\n",
+                "LF:5\n",
+                "LH:4\n",
+                "end_of_record\n",
+            ),
+            script
+        )
+    );
+}
+
+#[test]
+fn trace_follows_calls_into_a_module() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/trace_module");
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(
+        format!("{}/util.lox", dir),
+        concat!(
+            "fun half(x) {\n",
+            "  let h = x / 2;\n",
+            "  return h;\n",
+            "}\n",
+        ),
+    )
+    .unwrap();
+    let script = format!("{}/main.lox", dir);
+    std::fs::write(
+        &script,
+        concat!(
+            "import \"util.lox\" as util;\n",
+            "fun quarter(x) {\n",
+            "  return util.half(util.half(x));\n",
+            "}\n",
+            "let a = quarter(8);\n",
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--trace", &script])
+        .output()
+        .expect("failed to run rlox");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        concat!(
+            "[line 1] import \"util.lox\" as util;\n",
+            "[util.lox line 1] fun half(x) {\n",
+            "  define half = <fun half>\n",
+            "  define util = <module util>\n",
+            "[line 2] fun quarter(x) {\n",
+            "  define quarter = <fun quarter>\n",
+            "[line 5] let a = quarter(8);\n",
+            "  call quarter(8)\n",
+            "    [line 3] return util.half(util.half(x));\n",
+            "      call half(8)\n",
+            "        [util.lox line 2] let h = x / 2;\n",
+            "          define h = 4\n",
+            "        [util.lox line 3] return h;\n",
+            "      return half => 4\n",
+            "      call half(4)\n",
+            "        [util.lox line 2] let h = x / 2;\n",
+            "          define h = 2\n",
+            "        [util.lox line 3] return h;\n",
+            "      return half => 2\n",
+            "  return quarter => 2\n",
+            "  define a = 2\n",
+        )
+    );
+}
+
+#[test]
+fn profile_and_coverage_attribute_module_code_to_its_file() {
+    let dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/tools_module");
+    std::fs::create_dir_all(dir).unwrap();
+    let module = format!("{}/shapes.lox", dir);
+    std::fs::write(
+        &module,
+        concat!(
+            "fun area(w, h) {\n",
+            "  if (w < 0) {\n",
+            "    return 0;\n",
+            "  }\n",
+            "  return w * h;\n",
+            "}\n",
+        ),
+    )
+    .unwrap();
+    let script = format!("{}/main.lox", dir);
+    std::fs::write(
+        &script,
+        concat!(
+            "import \"shapes.lox\" as shapes;\n",
+            "fun area(r) {\n",
+            "  return 3 * r * r;\n",
+            "}\n",
+            "println(area(1) + shapes.area(2, 3));\n",
+        ),
+    )
+    .unwrap();
+
+    let folded = format!("{}/profile.folded", dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--profile", "--profile-output", &folded, &script])
+        .output()
+        .expect("failed to run rlox");
+    assert!(output.status.success());
+    let stacks = std::fs::read_to_string(&folded).unwrap();
+    let stacks = stacks
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stacks,
+        ["<script>", "<script>;area:2", "<script>;area:shapes.lox:1"]
+    );
+
+    let lcov = format!("{}/lcov.info", dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--coverage", "--coverage-output", &lcov, &script])
+        .output()
+        .expect("failed to run rlox");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            concat!(
+                "lines:    7/8 (87.5%)\n",
+                "branches: 1/2 (50.0%)\n",
+                "uncovered lines: shapes.lox:3\n",
+                "lcov tracefile written to {}\n",
+            ),
+            lcov
+        )
+    );
+    let records = std::fs::read_to_string(&lcov).unwrap();
+    let files = records
+        .lines()
+        .filter_map(|line| line.strip_prefix("SF:"))
+        .collect::<Vec<_>>();
+    let module = std::fs::canonicalize(&module).unwrap();
+    assert_eq!(files, [script.as_str(), &module.display().to_string()]);
+    assert!(
+        records.ends_with(concat!(
+            "BRDA:2,0,0,0\n",
+            "BRDA:2,0,1,1\n",
+            "BRF:2\n",
+            "BRH:1\n",
+            "DA:1,1\n",
+            "DA:2,1\n",
+            "DA:3,0\n",
+            "DA:5,1\n",
+            "LF:4\n",
+            "LH:3\n",
+            "end_of_record\n",
+        )),
+        "{}",
+        records
+    );
+}
+
+#[test]
+fn repeating_a_string_past_the_limit_is_a_runtime_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args([
+            "-e",
+            "repeat(\"a\", 1000000000000000000);\nrepeat(\"ab\", 1073741824);\nprintln(len(repeat(\"ab\", 3)));\n",
+        ])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Runtime produced 1 error:\n",
+            "    ERROR: String too long => repeat would make more than 1073741824 bytes\n",
+            "Runtime produced 1 error:\n",
+            "    ERROR: String too long => repeat would make more than 1073741824 bytes\n",
+            "6\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn runtime_errors_end_the_enclosing_block_and_loop() {
+    let source = concat!(
+        "let i = 0;\n",
+        "while (i < 10) {\n",
+        "  if (i == 2) {\n",
+        "    missing();\n",
+        "  }\n",
+        "  i = i + 1;\n",
+        "}\n",
+        "println(i);\n",
+        "{\n",
+        "  println(\"before\");\n",
+        "  missing();\n",
+        "  println(\"skipped\");\n",
+        "}\n",
+        "println(\"next statement\");\n",
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", source])
+        .output()
+        .expect("failed to run rlox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            "Runtime produced 1 error:\n",
+            "    ERROR: Undefined variable => missing\n",
+            "2\n",
+            "before\n",
+            "Runtime produced 1 error:\n",
+            "    ERROR: Undefined variable => missing\n",
+            "next statement\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(70));
+}